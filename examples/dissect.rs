@@ -0,0 +1,79 @@
+use anyhow::{Result, anyhow};
+use std::env;
+use m365::capture::{Capture, CaptureDirection, CaptureKeys, hex_bytes};
+use m365::dissector::{dissect_capture, Dissector};
+use m365::mi_crypto::LoginKeychain;
+
+/**
+ * Keys as 40 bytes of hex: dev key, dev iv, app key, app iv
+ */
+fn parse_keys(hex: &str) -> Result<LoginKeychain> {
+  let bytes = parse_hex(hex)?;
+  if bytes.len() != 40 {
+    return Err(anyhow!("Keys need 40 bytes: dev key, dev iv, app key and app iv"))
+  }
+
+  let keys = CaptureKeys {
+    dev_key: bytes[0..16].to_vec(),
+    dev_iv: bytes[16..20].to_vec(),
+    app_key: bytes[20..36].to_vec(),
+    app_iv: bytes[36..40].to_vec()
+  };
+
+  keys.keychain().ok_or_else(|| anyhow!("Invalid keys"))
+}
+
+/**
+ * Hex bytes, optionally split with spaces or colons like in btsnoop dumps
+ */
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+  let digits : String = hex.split(|char: char| char.is_whitespace() || char == ':').collect();
+  hex_bytes::decode(&digits).ok_or_else(|| anyhow!("Invalid hex: {}", hex.trim()))
+}
+
+/**
+ * Text dump with one chunk per line: "> 55ab..." for bytes written by app and "< 55ab..." for notifications
+ */
+fn dissect_dump(path: &str, keys: &LoginKeychain) -> Result<()> {
+  let mut dissector = Dissector::new(keys);
+
+  for line in std::fs::read_to_string(path)?.lines() {
+    let (origin, hex) = match line.trim().split_at_checked(1) {
+      Some((">", hex)) => (CaptureDirection::Write, hex),
+      Some(("<", hex)) => (CaptureDirection::Notification, hex),
+      _ => continue
+    };
+
+    for frame in dissector.push(origin, None, &parse_hex(hex)?) {
+      println!("{}", frame);
+    }
+  }
+
+  Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  let args: Vec<String> = env::args().collect();
+  if args.len() < 2 {
+    panic!("Usage: dissect <capture.json> [keys] or dissect <dump.txt> <keys>");
+  }
+
+  let keys = args.get(2).map(|keys| parse_keys(keys)).transpose()?;
+
+  if args[1].ends_with(".json") {
+    let capture = Capture::load(&args[1]).await?;
+    let keys = keys
+      .or_else(|| capture.keys.as_ref().and_then(|keys| keys.keychain()))
+      .ok_or_else(|| anyhow!("Capture has no keys, pass them as second argument"))?;
+
+    for frame in dissect_capture(&capture, &keys) {
+      println!("{}", frame);
+    }
+
+    Ok(())
+  } else {
+    let keys = keys.ok_or_else(|| anyhow!("Pass keys as second argument"))?;
+    dissect_dump(&args[1], &keys)
+  }
+}
@@ -1,31 +1,14 @@
 use anyhow::Result;
 use btleplug::api::{BDAddr};
-use tracing_subscriber;
-use tokio::io::AsyncReadExt;
-use std::path::Path;
-use tokio::fs::File;
 use tracing::Level;
 use std::env;
+use std::sync::Arc;
 use tracing_subscriber::fmt::format::FmtSpan;
 use m365::{
-  AuthToken,
-  ScooterScanner,
-  LoginRequest,
-  ConnectionHelper
+  Scooter,
+  FilePairingStore
 };
 
-async fn load_token() -> Result<AuthToken> {
-  let path = Path::new(".mi-token");
-  tracing::debug!("Opening token: {:?}", path);
-
-  let mut f = File::open(path).await?;
-  let mut buffer : AuthToken = [0; 12];
-
-  f.read(&mut buffer).await?;
-
-  Ok(buffer)
-}
-
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()>{
   tracing_subscriber::fmt()
@@ -38,19 +21,9 @@ async fn main() -> Result<()>{
     panic!("First argument is scooter mac address");
   }
 
-  let token = load_token().await?;
-
   let mac = BDAddr::from_str_delim(&args[1]).expect("Invalid mac address");
-  tracing::info!("Searching scooter with address: {}", mac);
-
-  let mut scanner = ScooterScanner::new().await?;
-  let scooter = scanner.wait_for(&mac).await?;
-  let device = scanner.peripheral(&scooter).await?;
-  let connection = ConnectionHelper::new(&device);
-  connection.reconnect().await?;
-
-  let mut request = LoginRequest::new(&device, &token).await?;
-  request.start().await?;
+  let store = Arc::new(FilePairingStore::new(".mi-pairings.json"));
+  let (_scooter, _session) = Scooter::connect(mac, store).start().await?;
 
   tracing::info!("Logged in with success! ");
   Ok(())
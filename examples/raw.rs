@@ -0,0 +1,63 @@
+use anyhow::{Result, Context};
+use btleplug::api::{BDAddr};
+use tracing::Level;
+use std::env;
+use std::sync::Arc;
+use tracing_subscriber::fmt::format::FmtSpan;
+use m365::{
+  Scooter,
+  FilePairingStore,
+  ScooterCommand,
+  ReadWrite
+};
+
+fn parse_byte(arg: &str) -> Result<u8> {
+  u8::from_str_radix(arg.trim_start_matches("0x"), 16)
+    .with_context(|| format!("Invalid hex byte: {}", arg))
+}
+
+fn parse_bytes(arg: &str) -> Result<Vec<u8>> {
+  (0..arg.len())
+    .step_by(2)
+    .map(|index| parse_byte(arg.get(index..index + 2).unwrap_or(&arg[index..])))
+    .collect()
+}
+
+/**
+ * Send any command to scooter and print response:
+ * cargo run --example raw <mac> <direction> <read/write> <address> [payload]
+ * for example, read firmware version: cargo run --example raw D5:01:45:37:ED:FD 20 01 1A 02
+ */
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<()>{
+  tracing_subscriber::fmt()
+    .with_max_level(Level::INFO)
+    .with_span_events(FmtSpan::CLOSE)
+    .init();
+
+  let args: Vec<String> = env::args().collect();
+  if args.len() < 5 {
+    panic!("Usage: raw <mac> <direction> <read/write> <address> [payload], numbers are in hex");
+  }
+
+  let mac = BDAddr::from_str_delim(&args[1]).expect("Invalid mac address");
+  let payload = match args.get(5) {
+    Some(payload) => parse_bytes(payload)?,
+    None => vec![]
+  };
+  let cmd = ScooterCommand::raw(parse_byte(&args[2])?, parse_byte(&args[3])?, parse_byte(&args[4])?, payload);
+
+  let store = Arc::new(FilePairingStore::new(".mi-pairings.json"));
+  let (scooter, mut session) = Scooter::connect(mac, store).start().await?;
+
+  tracing::info!("-> {:?}", cmd);
+  if cmd.read_write == ReadWrite::Read {
+    let response = session.request(&cmd).await?;
+    tracing::info!("<- {:?} {:?} {:#04x}: {:02x?}", response.direction, response.read_write, response.address, response.data);
+  } else {
+    session.send(&cmd).await?;
+  }
+
+  scooter.disconnect().await?;
+  Ok(())
+}
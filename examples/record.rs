@@ -0,0 +1,50 @@
+use anyhow::Result;
+use btleplug::api::{BDAddr};
+use tracing::Level;
+use std::env;
+use std::sync::Arc;
+use futures::StreamExt;
+use tracing_subscriber::fmt::format::FmtSpan;
+use m365::{
+  Scooter,
+  FilePairingStore,
+  TelemetryConfig,
+  RideRecorder
+};
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<()>{
+  tracing_subscriber::fmt()
+    .with_max_level(Level::INFO)
+    .with_span_events(FmtSpan::CLOSE)
+    .init();
+
+  let args: Vec<String> = env::args().collect();
+  if args.len() < 3 {
+    panic!("Usage: record <mac> <ride.ndjson|ride.csv>");
+  }
+
+  let mac = BDAddr::from_str_delim(&args[1]).expect("Invalid mac address");
+  let store = Arc::new(FilePairingStore::new(".mi-pairings.json"));
+  let (_scooter, mut session) = Scooter::connect(mac, store).start().await?;
+
+  let header = session.ride_header().await?;
+  let mut recorder = RideRecorder::create(&args[2], &header)?;
+  tracing::info!("Recording ride of {} to {}, press ctrl+c to stop", header.serial, args[2]);
+
+  let mut telemetry = Box::pin(session.telemetry_stream(TelemetryConfig::default())?);
+
+  loop {
+    tokio::select! {
+      _ = tokio::signal::ctrl_c() => break,
+      sample = telemetry.next() => match sample {
+        Some(Ok(sample)) => recorder.record(&sample)?,
+        Some(Err(error)) => tracing::error!("Scooter disconnected: {}", error),
+        None => break
+      }
+    }
+  }
+
+  recorder.finish()?;
+  Ok(())
+}
@@ -3,43 +3,39 @@ use tracing_subscriber::fmt::format::FmtSpan;
 
 use btleplug::platform::{Peripheral};
 use btleplug::api::BDAddr;
-use tokio::io::{BufWriter, AsyncWriteExt};
-use tokio::fs::File;
 use pretty_hex::*;
 use std::env;
-use tracing_subscriber;
-use std::path::Path;
 use anyhow::Result;
 
 use m365::{
-  ScooterScanner, ScannerEvent,
+  ScooterScanner, ScannerEvent, TrackedDevice,
   RegistrationRequest, RegistrationError,
-  ConnectionHelper, AuthToken
+  ConnectionHelper, AuthToken,
+  Pairing, PairingStore, FilePairingStore
 };
 
-async fn save_token(token : &AuthToken) -> Result<()> {
-  let path = Path::new(".mi-token");
-  tracing::info!("Saving token at {:?} with content {:?}", path, token.hex_dump());
-  let f = File::create(path).await?;
-  {
-    let mut writer = BufWriter::new(f);
-    writer.write(token).await?;
-    writer.flush().await?;
-  }
+async fn save_token(scooter: &TrackedDevice, token : &AuthToken) -> Result<()> {
+  let store = FilePairingStore::new(".mi-pairings.json");
+  tracing::info!("Saving token at {:?} with content {:?}", store.path(), token.hex_dump());
+
+  let mut pairing = Pairing::new(scooter.addr, *token);
+  pairing.device_name = scooter.name.clone();
+  store.save(pairing).await?;
+
   Ok(())
 }
 
-async fn register(device: &Peripheral) -> Result<()> {
-  let connection = ConnectionHelper::new(&device);
+async fn register(device: &Peripheral, scooter: &TrackedDevice) -> Result<()> {
+  let connection = ConnectionHelper::new(device);
 
   loop {
     tracing::info!(">>> Press power button up to 5 seconds after beep!");
     connection.reconnect().await?;
-    let mut request = RegistrationRequest::new(&device).await?;
+    let mut request = RegistrationRequest::new(device).await?;
 
     match request.start().await {
       Ok(token) => {
-        save_token(&token).await?;
+        save_token(scooter, &token).await?;
         break;
       },
       Err(RegistrationError::RestartNeeded) => {
@@ -79,7 +75,7 @@ async fn main() -> Result<()> {
         if scooter.addr == mac {
           tracing::info!("Found your scooter, starting registration");
           let device = scanner.peripheral(&scooter).await?;
-          register(&device).await?;
+          register(&device, &scooter).await?;
           break;
         } else {
           tracing::info!("Found scooter nearby: {} with mac: {}", scooter.name.unwrap(), scooter.addr);
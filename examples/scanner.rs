@@ -1,4 +1,3 @@
-use tracing_subscriber;
 use tracing::Level;
 use tracing_subscriber::fmt::format::FmtSpan;
 use anyhow::Result;
@@ -1,35 +1,19 @@
-use anyhow::{Result, Context};
+use anyhow::Result;
 use btleplug::api::{BDAddr};
-use tracing_subscriber;
-use tokio::io::AsyncReadExt;
-use std::path::Path;
-use tokio::fs::File;
 use tracing::Level;
 use std::env;
+use std::sync::Arc;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tokio::time;
 use std::time::Duration;
 
 use m365::{
-  AuthToken,
-  ScooterScanner,
-  LoginRequest,
-  ConnectionHelper,
-  TailLight
+  Scooter,
+  FilePairingStore,
+  TailLight,
+  Kers
 };
 
-async fn load_token() -> Result<AuthToken> {
-  let path = Path::new(".mi-token");
-  tracing::debug!("Opening token: {:?}", path);
-
-  let mut f = File::open(path).await?;
-  let mut buffer : AuthToken = [0; 12];
-
-  f.read(&mut buffer).await?;
-
-  Ok(buffer)
-}
-
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()>{
   tracing_subscriber::fmt()
@@ -42,20 +26,9 @@ async fn main() -> Result<()>{
     panic!("First argument is scooter mac address");
   }
 
-  let token = load_token().await
-    .with_context(|| "Could not load registration token")?;
-
   let mac = BDAddr::from_str_delim(&args[1]).expect("Invalid mac address");
-  tracing::info!("Searching scooter with address: {}", mac);
-
-  let mut scanner = ScooterScanner::new().await?;
-  let scooter = scanner.wait_for(&mac).await?;
-  let device = scanner.peripheral(&scooter).await?;
-  let connection = ConnectionHelper::new(&device);
-  connection.reconnect().await?;
-
-  let mut request = LoginRequest::new(&device, &token).await?;
-  let mut session = request.start().await?;
+  let store = Arc::new(FilePairingStore::new(".mi-pairings.json"));
+  let (_scooter, mut session) = Scooter::connect(mac, store).start().await?;
 
   tracing::info!("Logged in with success, reading data...");
 
@@ -80,5 +53,19 @@ async fn main() -> Result<()>{
   tracing::info!("  Tail light enabled: {:?}", session.tail_light().await?);
   tracing::info!("  Supplementary info {:?}", session.supplementary_info().await?);
 
+  let kers = session.kers().await?;
+  tracing::info!("  Kers: {:?}, Switching to strong", kers);
+  session.set_kers(Kers::Strong).await?;
+  tracing::info!("  Kers: {:?}, Switching back", session.kers().await?);
+  session.set_kers(kers).await?;
+
+  tracing::info!("  Locked: {}, Locking", session.is_locked().await?);
+  session.lock().await?;
+  tracing::info!("  Locked: {}, Unlocking", session.is_locked().await?);
+  session.unlock().await?;
+  tracing::info!("  Locked: {}", session.is_locked().await?);
+
+  tracing::info!("  Speed profile: {:?}", session.speed_profile().await?);
+
   Ok(())
 }
@@ -1,41 +1,16 @@
 use anyhow::Result;
 use btleplug::api::{BDAddr};
-use tracing_subscriber;
-use tokio::io::AsyncReadExt;
-use std::path::Path;
-use tokio::fs::File;
 use tracing::Level;
 use std::env;
-use tokio::time;
-use std::time::Duration;
+use std::sync::Arc;
+use futures::StreamExt;
 use tracing_subscriber::fmt::format::FmtSpan;
 use m365::{
-  AuthToken,
-  ScooterScanner,
-  LoginRequest,
-  ConnectionHelper,
-  MiSession
+  Scooter,
+  FilePairingStore,
+  TelemetryConfig
 };
 
-async fn load_token() -> Result<AuthToken> {
-  let path = Path::new(".mi-token");
-  tracing::debug!("Opening token: {:?}", path);
-
-  let mut f = File::open(path).await?;
-  let mut buffer : AuthToken = [0; 12];
-
-  f.read(&mut buffer).await?;
-
-  Ok(buffer)
-}
-
-async fn read(session : &mut MiSession) -> Result<()> {
-  tracing::info!("  Current Speed {} km/h", session.speed().await?);
-  tracing::info!("  Motor info: {:?}", session.motor_info().await?);
-  tracing::info!("  Battery info: {:?}", session.battery_info().await?);
-  Ok(())
-}
-
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()>{
   tracing_subscriber::fmt()
@@ -48,24 +23,30 @@ async fn main() -> Result<()>{
     panic!("First argument is scooter mac address");
   }
 
-  let token = load_token().await?;
-
   let mac = BDAddr::from_str_delim(&args[1]).expect("Invalid mac address");
-  tracing::info!("Searching scooter with address: {}", mac);
-
-  let mut scanner = ScooterScanner::new().await?;
-  let scooter = scanner.wait_for(&mac).await?;
-  let device = scanner.peripheral(&scooter).await?;
-  let connection = ConnectionHelper::new(&device);
-  connection.reconnect().await?;
-
-  let mut request = LoginRequest::new(&device, &token).await?;
-  let mut session = request.start().await?;
+  let store = Arc::new(FilePairingStore::new(".mi-pairings.json"));
+  let (_scooter, mut session) = Scooter::connect(mac, store).start().await?;
 
   tracing::info!("Logged in with success, reading data...");
 
-  loop {
-    read(&mut session).await;
-    time::sleep(Duration::from_millis(1000)).await;
+  let mut telemetry = Box::pin(session.telemetry_stream(TelemetryConfig::default())?);
+
+  while let Some(sample) = telemetry.next().await {
+    match sample {
+      Ok(sample) => {
+        if let Some(motor) = sample.motor {
+          tracing::info!("  Current Speed {} km/h", motor.speed_kmh);
+          tracing::info!("  Motor info: {:?}", motor);
+        }
+        tracing::info!("  Battery info: {:?}", sample.battery);
+
+        for failure in sample.failures {
+          tracing::warn!("Could not read {:?}: {}", failure.group, failure.error);
+        }
+      },
+      Err(error) => tracing::error!("Scooter disconnected: {}", error)
+    }
   }
+
+  Ok(())
 }
@@ -0,0 +1,488 @@
+/*!
+ * Recording of everything written to and received from scooter, and transport that plays it back.
+ *
+ * Wrap any transport with [`CaptureTransport`] to record a session, save it to file and later feed it to
+ * [`ReplayTransport`] to reproduce the same exchange without scooter.
+ */
+use crate::consts::Registers;
+use crate::dissector::{dissect_frame, DissectedFrame, FrameAssembler};
+use crate::mi_crypto::{LoginKeychain, EncryptionKey};
+use crate::transport::{ScooterTransport, NotificationStream};
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use anyhow::Result;
+use btleplug::api::ValueNotification;
+use futures::StreamExt;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use uuid::Uuid;
+
+/**
+ * Bump when layout of capture file changes
+ */
+const FORMAT_VERSION : u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum CaptureError {
+  #[error("Capture file has unsupported format version: {0}")]
+  UnsupportedVersion(u32),
+  #[error("Capture file is corrupted: {0}")]
+  Corrupted(serde_json::Error),
+  #[error("Could not access capture file: {0}")]
+  Io(std::io::Error)
+}
+
+impl From<std::io::Error> for CaptureError {
+  fn from(other: std::io::Error) -> Self {
+    CaptureError::Io(other)
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+  #[error("Capture has no more writes, but app wrote {data} to {uuid}")]
+  Exhausted { uuid: Uuid, data: String },
+  #[error("Capture expected write to {expected}, but app wrote to {received}")]
+  UnexpectedWrite { expected: Uuid, received: Uuid },
+  #[error("Capture expected write of {expected}, but app wrote {received}")]
+  UnexpectedData { expected: String, received: String },
+  #[error("Capture expected command {expected}, but app sent {received}")]
+  UnexpectedCommand { expected: String, received: String }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaptureDirection {
+  /**
+   * Bytes written by app to scooter
+   */
+  Write,
+  /**
+   * Notification sent by scooter to app
+   */
+  Notification
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureEvent {
+  pub direction: CaptureDirection,
+  pub uuid: Uuid,
+  /**
+   * Milliseconds since capture started
+   */
+  pub timestamp_ms: u64,
+  #[serde(with = "hex_bytes")]
+  pub data: Vec<u8>
+}
+
+/**
+ * Keys derived during login, only stored when user allows it. Anyone with them can decrypt whole capture
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureKeys {
+  #[serde(with = "hex_bytes")]
+  pub dev_key: Vec<u8>,
+  #[serde(with = "hex_bytes")]
+  pub dev_iv: Vec<u8>,
+  #[serde(with = "hex_bytes")]
+  pub app_key: Vec<u8>,
+  #[serde(with = "hex_bytes")]
+  pub app_iv: Vec<u8>
+}
+
+impl From<&LoginKeychain> for CaptureKeys {
+  fn from(keys: &LoginKeychain) -> Self {
+    Self {
+      dev_key: keys.dev.key.to_vec(),
+      dev_iv: keys.dev.iv.to_vec(),
+      app_key: keys.app.key.to_vec(),
+      app_iv: keys.app.iv.to_vec()
+    }
+  }
+}
+
+impl CaptureKeys {
+  /**
+   * Keychain for MiSession, None when some key has wrong length
+   */
+  pub fn keychain(&self) -> Option<LoginKeychain> {
+    Some(LoginKeychain {
+      dev: EncryptionKey { key: self.dev_key.clone().try_into().ok()?, iv: self.dev_iv.clone().try_into().ok()? },
+      app: EncryptionKey { key: self.app_key.clone().try_into().ok()?, iv: self.app_iv.clone().try_into().ok()? }
+    })
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+  pub version: u32,
+  /**
+   * Milliseconds since unix epoch
+   */
+  pub started_at_ms: u64,
+  pub keys: Option<CaptureKeys>,
+  pub events: Vec<CaptureEvent>
+}
+
+impl Default for Capture {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Capture {
+  pub fn new() -> Self {
+    let started_at_ms = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_millis() as u64)
+      .unwrap_or(0);
+
+    Self { version: FORMAT_VERSION, started_at_ms, keys: None, events: Vec::new() }
+  }
+
+  pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self, CaptureError> {
+    let bytes = tokio::fs::read(path).await?;
+
+    let version : serde_json::Value = serde_json::from_slice(&bytes)
+      .map_err(CaptureError::Corrupted)?;
+    let version = version["version"].as_u64().unwrap_or(0) as u32;
+
+    if version != FORMAT_VERSION {
+      return Err(CaptureError::UnsupportedVersion(version))
+    }
+
+    serde_json::from_slice(&bytes).map_err(CaptureError::Corrupted)
+  }
+
+  pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CaptureError> {
+    let bytes = serde_json::to_vec_pretty(self).map_err(CaptureError::Corrupted)?;
+    tokio::fs::write(path, bytes).await?;
+    Ok(())
+  }
+
+  /**
+   * Only uart traffic, without registration and login. Replay it with stored keys, because login can't be
+   * replayed: app picks random key every time
+   */
+  pub fn uart_only(&self) -> Self {
+    let uart = [Registers::TX.to_uuid(), Registers::RX.to_uuid()];
+
+    Self {
+      events: self.events.iter().filter(|event| uart.contains(&event.uuid)).cloned().collect(),
+      ..self.clone()
+    }
+  }
+}
+
+struct Recorder {
+  capture: Capture,
+  started: Instant
+}
+
+impl Recorder {
+  fn push(&mut self, direction: CaptureDirection, uuid: Uuid, data: &[u8]) {
+    let timestamp_ms = self.started.elapsed().as_millis() as u64;
+    self.capture.events.push(CaptureEvent { direction, uuid, timestamp_ms, data: data.to_vec() });
+  }
+}
+
+/**
+ * Transport that records every write and notification of wrapped transport
+ *
+ * ```no_run
+ * # async fn run(device: btleplug::platform::Peripheral, token: m365::AuthToken) -> anyhow::Result<()> {
+ * use m365::LoginRequest;
+ * use m365::capture::CaptureTransport;
+ *
+ * let transport = CaptureTransport::new(device);
+ * let mut session = LoginRequest::new(&transport, &token).await?.start().await?;
+ * transport.store_keys(session.keychain());
+ *
+ * session.motor_info().await?;
+ * transport.capture().save("motor_info.json").await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Clone)]
+pub struct CaptureTransport<T: ScooterTransport> {
+  inner: T,
+  recorder: Arc<Mutex<Recorder>>
+}
+
+impl<T: ScooterTransport> CaptureTransport<T> {
+  pub fn new(inner: T) -> Self {
+    let recorder = Recorder { capture: Capture::new(), started: Instant::now() };
+    Self { inner, recorder: Arc::new(Mutex::new(recorder)) }
+  }
+
+  pub fn inner(&self) -> &T {
+    &self.inner
+  }
+
+  /**
+   * Store keys derived during login in capture, so uart traffic can be decrypted and replayed later
+   */
+  pub fn store_keys(&self, keys: &LoginKeychain) {
+    self.recorder().capture.keys = Some(CaptureKeys::from(keys));
+  }
+
+  /**
+   * Copy of everything recorded so far
+   */
+  pub fn capture(&self) -> Capture {
+    self.recorder().capture.clone()
+  }
+
+  fn recorder(&self) -> std::sync::MutexGuard<'_, Recorder> {
+    self.recorder.lock().expect("Capture recorder lock is poisoned")
+  }
+}
+
+#[async_trait]
+impl<T: ScooterTransport + 'static> ScooterTransport for CaptureTransport<T> {
+  /**
+   * Notifications are recorded as soon as scooter sends them, not when they are consumed,
+   * so capture keeps their real order and time against writes
+   */
+  async fn notifications(&self) -> Result<NotificationStream> {
+    let recorder = self.recorder.clone();
+    let mut stream = self.inner.notifications().await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+      loop {
+        // Stop as soon as consumer drops its stream, otherwise next notification would be recorded for nobody
+        let notification = tokio::select! {
+          notification = stream.next() => notification,
+          _ = tx.closed() => None
+        };
+        let Some(notification) = notification else { break };
+
+        recorder.lock()
+          .expect("Capture recorder lock is poisoned")
+          .push(CaptureDirection::Notification, notification.uuid, &notification.value);
+
+        if tx.send(notification).is_err() {
+          break
+        }
+      }
+    });
+
+    Ok(Box::pin(UnboundedReceiverStream::new(rx)))
+  }
+
+  /**
+   * Write is recorded only when inner transport accepted it, so failed writes are not replayed later
+   */
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()> {
+    self.inner.write(reg, data).await?;
+    self.recorder().push(CaptureDirection::Write, reg.to_uuid(), data);
+    Ok(())
+  }
+
+  async fn unsubscribe(&self) -> Result<()> {
+    self.inner.unsubscribe().await
+  }
+}
+
+struct Replay {
+  events: VecDeque<CaptureEvent>,
+  subscriber: Option<mpsc::UnboundedSender<ValueNotification>>,
+  strict: bool,
+  commands: Option<CommandMatcher>
+}
+
+/**
+ * Uart frames are written in chunks, so recorded and written chunks are joined into frames before they are decrypted
+ * and compared
+ */
+struct CommandMatcher {
+  keys: LoginKeychain,
+  expected: FrameAssembler,
+  received: FrameAssembler,
+  pending_expected: VecDeque<Vec<u8>>,
+  pending_received: VecDeque<Vec<u8>>
+}
+
+impl CommandMatcher {
+  fn new(keys: LoginKeychain) -> Self {
+    Self {
+      keys,
+      expected: FrameAssembler::new(),
+      received: FrameAssembler::new(),
+      pending_expected: VecDeque::new(),
+      pending_received: VecDeque::new()
+    }
+  }
+
+  /**
+   * Compare direction, read/write and attribute of every frame completed by these chunks
+   */
+  fn push(&mut self, expected: &[u8], received: &[u8]) -> Result<(), ReplayError> {
+    self.pending_expected.extend(self.expected.push(expected));
+    self.pending_received.extend(self.received.push(received));
+
+    while !self.pending_expected.is_empty() && !self.pending_received.is_empty() {
+      let expected = self.pending_expected.pop_front().unwrap();
+      let received = self.pending_received.pop_front().unwrap();
+      let (expected, received) = (self.describe(&expected), self.describe(&received));
+
+      if expected != received {
+        return Err(ReplayError::UnexpectedCommand { expected, received })
+      }
+    }
+
+    Ok(())
+  }
+
+  fn describe(&self, frame: &[u8]) -> String {
+    match dissect_frame(frame, CaptureDirection::Write, &self.keys) {
+      DissectedFrame { direction: Some(direction), read_write: Some(read_write), address: Some(address), .. } => {
+        format!("{:?} {:?} {:#04x}", direction, read_write, address)
+      },
+      DissectedFrame { error, .. } => format!("frame that can't be decrypted: {}", error.unwrap_or_default())
+    }
+  }
+}
+
+impl Replay {
+  /**
+   * Send notifications that scooter sent before next write
+   */
+  fn flush_notifications(&mut self) {
+    while let Some(event) = self.events.front() {
+      if event.direction == CaptureDirection::Write {
+        return
+      }
+
+      let event = self.events.pop_front().unwrap();
+      if let Some(tx) = &self.subscriber {
+        let _ = tx.send(ValueNotification { uuid: event.uuid, value: event.data });
+      }
+    }
+  }
+}
+
+/**
+ * Transport that plays capture back. Every write is matched with next recorded write and answered with notifications
+ * that followed it. Uart frames carry random bytes, so only register is compared unless strict mode is enabled.
+ * When capture has keys, uart writes are decrypted and their direction, read/write and attribute are compared too
+ */
+#[derive(Clone)]
+pub struct ReplayTransport {
+  replay: Arc<Mutex<Replay>>
+}
+
+impl ReplayTransport {
+  pub fn new(capture: Capture) -> Self {
+    let commands = capture.keys.and_then(|keys| keys.keychain()).map(CommandMatcher::new);
+    let replay = Replay { events: capture.events.into(), subscriber: None, strict: false, commands };
+    Self { replay: Arc::new(Mutex::new(replay)) }
+  }
+
+  /**
+   * Compare written bytes too, useful for captures of exchanges without random data
+   */
+  pub fn strict(self) -> Self {
+    self.replay().strict = true;
+    self
+  }
+
+  /**
+   * Number of recorded events that were not played yet
+   */
+  pub fn remaining(&self) -> usize {
+    self.replay().events.len()
+  }
+
+  fn replay(&self) -> std::sync::MutexGuard<'_, Replay> {
+    self.replay.lock().expect("Replay lock is poisoned")
+  }
+}
+
+#[async_trait]
+impl ScooterTransport for ReplayTransport {
+  async fn notifications(&self) -> Result<NotificationStream> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let mut replay = self.replay();
+    replay.subscriber = Some(tx);
+    replay.flush_notifications();
+
+    Ok(Box::pin(UnboundedReceiverStream::new(rx)))
+  }
+
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()> {
+    let mut replay = self.replay();
+    let uuid = reg.to_uuid();
+
+    let expected = match replay.events.pop_front() {
+      Some(event) => event,
+      None => return Err(ReplayError::Exhausted { uuid, data: hex_bytes::encode(data) }.into())
+    };
+
+    if expected.uuid != uuid {
+      return Err(ReplayError::UnexpectedWrite { expected: expected.uuid, received: uuid }.into())
+    }
+
+    if replay.strict && expected.data != data {
+      return Err(ReplayError::UnexpectedData {
+        expected: hex_bytes::encode(&expected.data),
+        received: hex_bytes::encode(data)
+      }.into())
+    }
+
+    if uuid == Registers::TX.to_uuid() {
+      if let Some(commands) = &mut replay.commands {
+        commands.push(&expected.data, data)?;
+      }
+    }
+
+    replay.flush_notifications();
+    Ok(())
+  }
+
+  async fn unsubscribe(&self) -> Result<()> {
+    self.replay().subscriber = None;
+    Ok(())
+  }
+}
+
+/**
+ * Bytes are stored as hex string, so capture can be read and edited by hand
+ */
+pub mod hex_bytes {
+  use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+  pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+  }
+
+  /**
+   * Bytes from hex string with two digits per byte and nothing else, None when string is not valid hex
+   */
+  pub fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.chars().all(|char| char.is_ascii_hexdigit()) || !hex.len().is_multiple_of(2) {
+      return None
+    }
+
+    (0..hex.len()).step_by(2)
+      .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+      .collect()
+  }
+
+  pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(bytes))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    decode(&hex).ok_or_else(|| D::Error::custom("invalid hex string"))
+  }
+}
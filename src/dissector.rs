@@ -0,0 +1,335 @@
+/*!
+ * Offline decoder of uart frames. Feed it with bytes written by app and notifications from scooter, for example
+ * from [`Capture`] or btsnoop log, together with keys derived during login, and it returns readable records.
+ */
+use crate::capture::{Capture, CaptureDirection};
+use crate::consts::Registers;
+use crate::mi_crypto::{decrypt_uart, uart_counter, crc16, LoginKeychain};
+use crate::protocol::{NB_HEADER, NB_FRAME_OVERHEAD};
+use crate::session::{Payload, MotorInfo, BatteryInfo, FirmwareVersion, Register, Direction, ReadWrite, Attribute};
+use crate::session::registers;
+
+use std::fmt;
+use anyhow::Result;
+
+type Describe = fn(Payload) -> Result<String>;
+
+/**
+ * Row of known registers for register constant, with its direction, address and decoder
+ */
+macro_rules! known {
+  ($name:literal, $register:path) => {
+    ($register.direction, $register.address, $name, Some(|payload| show(&$register, payload)))
+  };
+}
+
+/**
+ * Registers that dissector knows by name: (direction of request, address, name, decoder of value)
+ */
+const KNOWN_REGISTERS : [(Direction, u8, &str, Option<Describe>); 27] = [
+  known!("SerialNumber", registers::SERIAL_NUMBER),
+  known!("EscVersion", registers::ESC_VERSION),
+  known!("DistanceLeft", registers::DISTANCE_LEFT),
+  known!("Trip", registers::TRIP),
+  known!("FrameTemperature", registers::FRAME_TEMPERATURE),
+  known!("BmsVersion", registers::BMS_VERSION),
+  known!("BleVersion", registers::BLE_VERSION),
+  (Direction::MasterToMotor, Attribute::Lock.value(), "Lock", None),
+  (Direction::MasterToMotor, Attribute::Unlock.value(), "Unlock", None),
+  known!("SpeedLimit", registers::SPEED_LIMIT),
+  (Direction::MasterToMotor, Attribute::PowerOff.value(), "PowerOff", None),
+  known!("Kers", registers::KERS),
+  known!("Cruise", registers::CRUISE),
+  known!("TailLight", registers::TAIL_LIGHT),
+  (Direction::MasterToMotor, Attribute::MotorInfo.value(), "MotorInfo", Some(|payload| Ok(format!("{:?}", MotorInfo::try_from(payload)?)))),
+  known!("StatusFlags", registers::STATUS_FLAGS),
+  known!("Speed", registers::SPEED),
+  known!("SpeedAverage", registers::SPEED_AVERAGE),
+  known!("TripDistance", registers::TRIP_DISTANCE),
+  (Direction::MasterToBattery, Attribute::GeneralInfo.value(), "BatteryPack", Some(battery_pack)),
+  known!("BatteryCycles", registers::BATTERY_CYCLES),
+  known!("BatteryManufactureDate", registers::BATTERY_MANUFACTURE_DATE),
+  (Direction::MasterToBattery, Attribute::BatteryInfo.value(), "BatteryInfo", Some(|payload| Ok(format!("{:?}", BatteryInfo::try_from(payload)?)))),
+  known!("BatteryPercent", registers::BATTERY_PERCENT),
+  known!("BatteryCurrent", registers::BATTERY_CURRENT),
+  known!("BatteryVoltage", registers::BATTERY_VOLTAGE),
+  (Direction::MasterToBattery, Attribute::BatteryCellVoltages.value(), "BatteryCellVoltages", Some(cell_voltages)),
+];
+
+fn show<V: fmt::Debug>(register: &Register<V>, payload: Payload) -> Result<String> {
+  Ok(format!("{:?}", register.decode(payload)?))
+}
+
+fn cell_voltages(mut payload: Payload) -> Result<String> {
+  payload.pop_head()?;
+
+  let mut cells = Vec::new();
+  while let Ok(voltage) = payload.pop_u16() {
+    cells.push(voltage as f32 / 1000.0);
+  }
+
+  Ok(format!("{:?}", cells))
+}
+
+/**
+ * Serial, firmware and capacities of battery pack. App reads different length of it, so words that were not read are skipped
+ */
+fn battery_pack(mut payload: Payload) -> Result<String> {
+  payload.pop_head()?;
+
+  let mut fields = vec![format!("serial: {:?}", payload.pop_string_utf8(14)?)];
+  if let Ok(version) = payload.pop_u16() {
+    fields.push(format!("firmware_version: {}", FirmwareVersion::from(version)));
+  }
+
+  for name in ["design_capacity", "full_charge_capacity"] {
+    if let Ok(capacity) = payload.pop_u16() {
+      fields.push(format!("{}: {}", name, capacity));
+    }
+  }
+
+  Ok(format!("BatteryPack {{ {} }}", fields.join(", ")))
+}
+
+fn is_battery(direction: Direction) -> bool {
+  matches!(direction, Direction::MasterToBattery | Direction::BatteryToMaster)
+}
+
+/**
+ * Name of register, from Attribute or wider table of known registers
+ */
+pub fn register_name(direction: Direction, address: u8) -> Option<&'static str> {
+  known_register(direction, address).map(|(name, _)| name)
+}
+
+fn known_register(direction: Direction, address: u8) -> Option<(&'static str, Option<Describe>)> {
+  let battery = is_battery(direction);
+
+  KNOWN_REGISTERS.iter()
+    .find(|(known_direction, known, _, _)| is_battery(*known_direction) == battery && *known == address)
+    .map(|(_, _, name, describe)| (*name, *describe))
+}
+
+/**
+ * Single uart frame with everything that could be decoded from it
+ */
+#[derive(Clone, Debug)]
+pub struct DissectedFrame {
+  /**
+   * Write means frame was sent by app, Notification that it came from scooter
+   */
+  pub origin: CaptureDirection,
+  /**
+   * Milliseconds since capture started, when known
+   */
+  pub timestamp_ms: Option<u64>,
+  pub counter: Option<u16>,
+  pub crc_valid: bool,
+  pub direction: Option<Direction>,
+  pub read_write: Option<ReadWrite>,
+  pub address: Option<u8>,
+  pub name: Option<&'static str>,
+  /**
+   * Decrypted bytes after direction, read/write and address
+   */
+  pub data: Vec<u8>,
+  pub value: Option<String>,
+  /**
+   * Why frame could not be decrypted or decoded
+   */
+  pub error: Option<String>,
+  pub raw: Vec<u8>
+}
+
+impl fmt::Display for DissectedFrame {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(timestamp_ms) = self.timestamp_ms {
+      write!(fmt, "{:>8}ms ", timestamp_ms)?;
+    }
+
+    let arrow = match self.origin {
+      CaptureDirection::Write => ">",
+      CaptureDirection::Notification => "<"
+    };
+    write!(fmt, "{} #{:<5} ", arrow, self.counter.map(|counter| counter.to_string()).unwrap_or_else(|| "?".to_string()))?;
+
+    match (self.direction, self.read_write, self.address) {
+      (Some(direction), Some(read_write), Some(address)) => {
+        write!(fmt, "{:?} {:?} {:#04x} {}", direction, read_write, address, self.name.unwrap_or("Unknown"))?;
+      },
+      _ => write!(fmt, "{:02x?}", self.raw)?
+    }
+
+    if let Some(value) = &self.value {
+      write!(fmt, ": {}", value)?;
+    } else if !self.data.is_empty() {
+      write!(fmt, ": {:02x?}", self.data)?;
+    }
+
+    if let Some(error) = &self.error {
+      write!(fmt, " ({})", error)?;
+    }
+
+    if !self.crc_valid {
+      write!(fmt, " [bad crc]")?;
+    }
+
+    Ok(())
+  }
+}
+
+/**
+ * Decrypt and decode single `55 AB` frame. Frames from app are encrypted with app key, frames from scooter with dev key
+ */
+pub fn dissect_frame(frame: &[u8], origin: CaptureDirection, keys: &LoginKeychain) -> DissectedFrame {
+  let crc_valid = frame.len() > 4 && crc16(&frame[2..frame.len() - 2]) == frame[frame.len() - 2..];
+
+  let mut dissected = DissectedFrame {
+    origin,
+    timestamp_ms: None,
+    counter: uart_counter(frame).ok(),
+    crc_valid,
+    direction: None,
+    read_write: None,
+    address: None,
+    name: None,
+    data: Vec::new(),
+    value: None,
+    error: None,
+    raw: frame.to_vec()
+  };
+
+  if frame.len() < NB_FRAME_OVERHEAD - 2 {
+    dissected.error = Some("frame is too short".to_string());
+    return dissected
+  }
+
+  let key = match origin {
+    CaptureDirection::Write => &keys.app,
+    CaptureDirection::Notification => &keys.dev
+  };
+
+  let msg = match decrypt_uart(key, frame) {
+    Ok(msg) if msg.len() >= 3 => msg,
+    Ok(_) => {
+      dissected.error = Some("decrypted frame is too short".to_string());
+      return dissected
+    },
+    Err(error) => {
+      dissected.error = Some(error.to_string());
+      return dissected
+    }
+  };
+
+  let direction = Direction::from(msg[0]);
+  let read_write = ReadWrite::from(msg[1]);
+  let address = msg[2];
+  let data_size = (frame[2] as usize).saturating_sub(2).min(msg.len() - 3);
+  let data = msg[3..3 + data_size].to_vec();
+
+  dissected.direction = Some(direction);
+  dissected.read_write = Some(read_write);
+  dissected.address = Some(address);
+  dissected.data = data.clone();
+
+  let known = known_register(direction, address);
+  dissected.name = known.map(|(name, _)| name);
+
+  let is_request = matches!(direction, Direction::MasterToMotor | Direction::MasterToBattery);
+  if is_request && read_write == ReadWrite::Read {
+    dissected.value = data.first().map(|length| format!("read {} bytes", length));
+    return dissected
+  }
+
+  if let Some((_, Some(describe))) = known {
+    match describe(Payload::from(&msg[0..3 + data_size])) {
+      Ok(value) => dissected.value = Some(value),
+      Err(error) => dissected.error = Some(error.to_string())
+    }
+  }
+
+  dissected
+}
+
+/**
+ * Joins chunks into whole `55 AB` frames. Bytes before frame header are dropped
+ */
+#[derive(Clone, Debug, Default)]
+pub struct FrameAssembler {
+  buffer: Vec<u8>
+}
+
+impl FrameAssembler {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Add chunk and return frames that are complete now
+   */
+  pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+    self.buffer.extend_from_slice(chunk);
+    let mut frames = Vec::new();
+
+    loop {
+      match self.buffer.windows(2).position(|window| window == NB_HEADER) {
+        Some(start) => { self.buffer.drain(..start); },
+        None => {
+          let keep = usize::from(self.buffer.last() == Some(&NB_HEADER[0]));
+          self.buffer.drain(..self.buffer.len() - keep);
+          return frames
+        }
+      }
+
+      if self.buffer.len() < 3 {
+        return frames
+      }
+
+      let frame_size = self.buffer[2] as usize + NB_FRAME_OVERHEAD;
+      if self.buffer.len() < frame_size {
+        return frames
+      }
+
+      frames.push(self.buffer.drain(..frame_size).collect());
+    }
+  }
+}
+
+/**
+ * Dissects stream of chunks sent in both directions
+ */
+pub struct Dissector {
+  keys: LoginKeychain,
+  from_app: FrameAssembler,
+  from_scooter: FrameAssembler
+}
+
+impl Dissector {
+  pub fn new(keys: &LoginKeychain) -> Self {
+    Self { keys: keys.clone(), from_app: FrameAssembler::new(), from_scooter: FrameAssembler::new() }
+  }
+
+  pub fn push(&mut self, origin: CaptureDirection, timestamp_ms: Option<u64>, chunk: &[u8]) -> Vec<DissectedFrame> {
+    let assembler = match origin {
+      CaptureDirection::Write => &mut self.from_app,
+      CaptureDirection::Notification => &mut self.from_scooter
+    };
+
+    assembler.push(chunk).into_iter()
+      .map(|frame| DissectedFrame { timestamp_ms, ..dissect_frame(&frame, origin, &self.keys) })
+      .collect()
+  }
+}
+
+/**
+ * Dissect uart traffic of whole capture, registration and login are skipped
+ */
+pub fn dissect_capture(capture: &Capture, keys: &LoginKeychain) -> Vec<DissectedFrame> {
+  let uart = [Registers::TX.to_uuid(), Registers::RX.to_uuid()];
+  let mut dissector = Dissector::new(keys);
+
+  capture.events.iter()
+    .filter(|event| uart.contains(&event.uuid))
+    .flat_map(|event| dissector.push(event.direction, Some(event.timestamp_ms), &event.data))
+    .collect()
+}
@@ -3,12 +3,20 @@ extern crate uuid;
 pub mod mi_crypto;
 pub mod protocol;
 pub mod consts;
+pub mod transport;
+pub mod simulator;
+pub mod pairing;
+pub mod capture;
+pub mod dissector;
 
 mod register;
 mod scanner;
 mod connection;
 mod login;
 mod session;
+mod scooter;
+mod supervisor;
+mod recorder;
 
 pub use register::RegistrationRequest as RegistrationRequest;
 pub use register::RegistrationError as RegistrationError;
@@ -19,12 +27,77 @@ pub use scanner::ScannerError as ScannerError;
 pub use scanner::ScannerEvent as ScannerEvent;
 pub use scanner::TrackedDevice as TrackedDevice;
 pub use connection::ConnectionHelper as ConnectionHelper;
+pub use transport::ScooterTransport as ScooterTransport;
+pub use scooter::{
+  Scooter,
+  ScooterConnector,
+  ScooterError,
+  ConnectStage,
+  TokenSource
+};
+pub use supervisor::{
+  SupervisedSession,
+  SupervisorConfig,
+  SessionEvent,
+  Reconnect
+};
+pub use recorder::{
+  RideRecorder,
+  RideFormat,
+  RideHeader,
+  RideSample,
+  Ride,
+  RideError
+};
+pub use pairing::{
+  Pairing,
+  PairingKey,
+  PairingStore,
+  PairingError,
+  FilePairingStore,
+  MemoryPairingStore
+};
 
 pub use session::{
   MiSession as MiSession,
+  SessionError,
   Payload,
   MotorInfo,
+  ErrorCode,
+  Phase,
+  Warnings,
+  StatusFlags,
+  WorkMode,
   GeneralInfo,
+  FirmwareVersion,
+  FirmwareVersions,
   TailLight,
-  BatteryInfo
+  Kers,
+  BatteryInfo,
+  BatteryPack,
+  ManufactureDate,
+  BatteryHealthReport,
+  HealthThresholds,
+  HealthFinding,
+  HealthIssue,
+  Severity,
+  TripInfo,
+  TelemetryConfig,
+  TelemetrySample,
+  TelemetryFailure,
+  TelemetryGroup,
+  ScooterModel,
+  Capabilities,
+  Feature,
+  SpeedProfile,
+  SpeedPolicy,
+  SpeedRule,
+  SpeedViolation,
+  Register,
+  ScooterCommand,
+  Direction,
+  ReadWrite,
+  Attribute,
+  RawResponse,
+  registers
 };
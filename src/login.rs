@@ -5,6 +5,7 @@ use crate::mi_crypto::{
 use crate::session::MiSession;
 use crate::consts::{MiCommands, Registers};
 use crate::protocol::MiProtocol;
+use crate::transport::ScooterTransport;
 use anyhow::Result;
 use pretty_hex::*;
 use btleplug::platform::Peripheral;
@@ -32,19 +33,19 @@ impl From<anyhow::Error> for LoginError {
  * If everything goes right, you will receive MiSession which allows you to send commands and read
  * read responses back from the scooter
  */
-pub struct LoginRequest {
-  protocol: MiProtocol,
+pub struct LoginRequest<T: ScooterTransport = Peripheral> {
+  protocol: MiProtocol<T>,
   auth_token: AuthToken,
   rand_key: RandKey,
-  device: Peripheral,
+  transport: T,
   remote_info: Option<[u8; 32]>,
   keys: Option<LoginKeychain>,
   remote_key: Option<Vec<u8>>,
 }
 
-impl LoginRequest {
-  pub async fn new(device : &Peripheral, token: &AuthToken) -> Result<Self> {
-    let protocol = MiProtocol::new(device).await?;
+impl<T: ScooterTransport> LoginRequest<T> {
+  pub async fn new(transport : &T, token: &AuthToken) -> Result<Self> {
+    let protocol = MiProtocol::new(transport).await?;
     let rand_key = gen_rand_key();
 
     Ok(
@@ -54,13 +55,13 @@ impl LoginRequest {
         keys: None,
         rand_key,
         protocol,
-        device: device.clone(),
-        auth_token: token.clone()
+        transport: transport.clone(),
+        auth_token: *token
       }
     )
   }
 
-  pub async fn start(&mut self) -> Result<MiSession> {
+  pub async fn start(&mut self) -> Result<MiSession<T>> {
     self.send_key().await?;
     self.read_remote_key().await?;
     self.read_remote_info().await?;
@@ -69,7 +70,7 @@ impl LoginRequest {
 
     self.protocol.dispose().await?;
     let keys = self.keys.as_ref().unwrap();
-    let session = MiSession::new(&self.device, keys).await?;
+    let session = MiSession::new(&self.transport, keys).await?;
     Ok(session)
   }
 
@@ -8,7 +8,7 @@ use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use p256::{PublicKey, ecdh::EphemeralSecret};
 use rand_core::{OsRng, RngCore};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use thiserror::Error;
 
 type HmacSha256 = Hmac<Sha256>;
@@ -20,6 +20,8 @@ pub enum MiCryptoError {
   InvalidHeader,
   #[error("Error when tried decrypt uart message: {0}")]
   DecryptUart(ccm::aead::Error),
+  #[error("Error when tried decrypt did: {0}")]
+  DecryptDid(ccm::aead::Error),
   #[error("Crypto Failure: {0}")]
   Other(anyhow::Error)
 }
@@ -47,9 +49,9 @@ fn encrypt_did(key: &[u8], did: &[u8]) -> Vec<u8> {
 
   let aes_ccm = AesCcm::new(key);
 
-  aes_ccm.encrypt(&nonce, Payload {
+  aes_ccm.encrypt(nonce, Payload {
     msg: did,
-    aad: aad
+    aad
   }).expect("Could not encrypt did")// output 48 bytes
 }
 
@@ -78,7 +80,7 @@ fn hash(secret : &[u8], data: &[u8]) -> Hash {
 
   let mut mac = HmacSha256::new_from_slice(secret)
     .expect("HMAC can take key of any size");
-  mac.update(&data);
+  mac.update(data);
   let result = mac.finalize();
 
   tracing::debug!("result= {:?}", result.clone().into_bytes().hex_dump());
@@ -92,7 +94,7 @@ pub fn calc_did(my_secret_key: &EphemeralSecret, remote_key_bytes: &[u8], remote
   let key_bytes = remote_key_bytes;
   tracing::debug!("Calculating did with remote key: {:?}", key_bytes.hex_dump());
 
-  let remote_public_key = PublicKey::from_sec1_bytes(&key_bytes).expect("Key sent by scooter is invalid");
+  let remote_public_key = PublicKey::from_sec1_bytes(key_bytes).expect("Key sent by scooter is invalid");
 
   let secret = my_secret_key.diffie_hellman(&remote_public_key);
   tracing::debug!("  eShareKey: {}", secret.as_bytes().hex_dump());
@@ -115,7 +117,43 @@ pub fn calc_did(my_secret_key: &EphemeralSecret, remote_key_bytes: &[u8], remote
   let mut final_token = [0u8; 12];
   final_token.copy_from_slice(token);
 
-  return (did_ct, final_token)
+  (did_ct, final_token)
+}
+
+/**
+ * Scooter side of calc_did. Derives token from our secret and app public key, then decrypts did sent by app
+ */
+pub fn calc_remote_did(my_secret_key: &EphemeralSecret, app_key_bytes: &[u8], did_ct: &[u8]) -> Result<(Vec<u8>, AuthToken), MiCryptoError> {
+  tracing::debug!("Calculating remote did with app key: {:?}", app_key_bytes.hex_dump());
+
+  let app_public_key = PublicKey::from_sec1_bytes(app_key_bytes)
+    .map_err(|_| MiCryptoError::Other(anyhow!("Key sent by app is invalid")))?;
+
+  let secret = my_secret_key.diffie_hellman(&app_public_key);
+  let derived_key = derive_key(secret.as_bytes(), None);
+
+  let token = &derived_key[0..12];
+  let a     = &derived_key[28..44];
+
+  let did = decrypt_did(a, did_ct)?;
+  tracing::debug!("  Did: {:?}", did.hex_dump());
+
+  let mut final_token = [0u8; 12];
+  final_token.copy_from_slice(token);
+
+  Ok((did, final_token))
+}
+
+fn decrypt_did(key: &[u8], did_ct: &[u8]) -> Result<Vec<u8>, MiCryptoError> {
+  let nonce = GenericArray::from_slice(&NONCE);
+  let key = GenericArray::from_slice(key);
+
+  let aes_ccm = AesCcm::new(key);
+
+  aes_ccm.decrypt(nonce, Payload {
+    msg: did_ct,
+    aad: b"devID"
+  }).map_err(MiCryptoError::DecryptDid)
 }
 
 #[derive(Clone)]
@@ -203,10 +241,14 @@ pub fn gen_rand_key() -> RandKey {
 
 const HEADER : [u8; 2] = [0x55, 0xab];
 
+/**
+ * Encrypt uart message. `it` is message counter, it should grow with every message sent in session,
+ * only two least significant bytes are sent in the frame, so it needs to stay below 0x10000
+ */
 pub fn encrypt_uart(encryption_key: &EncryptionKey, msg: &[u8], it : u32, rand: Option<[u8; 4]>) -> Vec<u8> {
   tracing::debug!("Encrypting UART");
 
-  let it = it.to_be_bytes();
+  let it = it.to_le_bytes();
 
   let rand = rand.or_else(|| {
     let mut rand : [u8; 4] = [0u8; 4];
@@ -231,15 +273,15 @@ pub fn encrypt_uart(encryption_key: &EncryptionKey, msg: &[u8], it : u32, rand:
 
   let mut nonce : Vec<u8> = Vec::new();
   nonce.extend_from_slice(&encryption_key.iv);
-  for _ in 0..4 { nonce.push(0); }
+  nonce.extend_from_slice(&[0; 4]);
   nonce.extend_from_slice(&it);
   tracing::debug!("  nonce: {:?}", nonce.hex_dump());
 
   let key = GenericArray::from_slice(&encryption_key.key);
   let nonce = GenericArray::from_slice(&nonce);
-  let aes_ccm = AesCcm::new(&key);
+  let aes_ccm = AesCcm::new(key);
 
-  let ct = aes_ccm.encrypt(&nonce, data.as_slice())
+  let ct = aes_ccm.encrypt(nonce, data.as_slice())
     .expect("Could not encrypt uart");// output 48 bytes
 
   tracing::debug!("  CT: {:?}", ct.hex_dump());
@@ -263,14 +305,23 @@ pub fn encrypt_uart(encryption_key: &EncryptionKey, msg: &[u8], it : u32, rand:
 }
 
 pub fn crc16(bytes: &[u8]) -> [u8; 2] {
-  let mut sum : i16 = 0;
+  let mut sum : u16 = 0;
   for byte in bytes {
-    sum += *byte as i16;
+    sum = sum.wrapping_add(*byte as u16);
+  }
+
+  (!sum).to_le_bytes()
+}
+
+/**
+ * Read message counter from encrypted uart frame
+ */
+pub fn uart_counter(msg: &[u8]) -> Result<u16, MiCryptoError> {
+  if msg.len() < 5 || msg[0..2] != HEADER {
+    return Err(MiCryptoError::InvalidHeader)
   }
 
-  let mut res = (-(sum) - 1).to_be_bytes();
-  res.reverse();
-  res
+  Ok(u16::from_le_bytes([msg[3], msg[4]]))
 }
 
 pub fn decrypt_uart(encryption_key: &EncryptionKey, msg: &[u8]) -> Result<Vec<u8>, MiCryptoError> {
@@ -291,9 +342,9 @@ pub fn decrypt_uart(encryption_key: &EncryptionKey, msg: &[u8]) -> Result<Vec<u8
 
   let mut nonce : Vec<u8> = Vec::new();
   nonce.extend_from_slice(&encryption_key.iv);
-  for _ in 0..4 { nonce.push(0); }
+  nonce.extend_from_slice(&[0; 4]);
   nonce.extend_from_slice(it);
-  for _ in 0..2 { nonce.push(0); }
+  nonce.extend_from_slice(&[0; 2]);
   tracing::debug!("  nonce: {:?}", nonce.hex_dump());
 
   let key = GenericArray::from_slice(&encryption_key.key);
@@ -0,0 +1,309 @@
+use crate::mi_crypto::AuthToken;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use btleplug::api::BDAddr;
+use serde::{Serialize, Deserialize};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::RwLock;
+use thiserror::Error;
+
+/**
+ * Version of file format written by FilePairingStore. Bump it when Pairing changes in incompatible way
+ */
+const FORMAT_VERSION : u32 = 1;
+
+/**
+ * Locks shared by every FilePairingStore in this process, one per file
+ */
+static FILE_LOCKS : OnceLock<Mutex<HashMap<PathBuf, Arc<RwLock<()>>>>> = OnceLock::new();
+
+fn file_lock(path: &Path) -> Arc<RwLock<()>> {
+  let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+  let mut locks = FILE_LOCKS.get_or_init(Default::default).lock().unwrap();
+
+  locks.entry(path).or_default().clone()
+}
+
+#[derive(Error, Debug)]
+pub enum PairingError {
+  #[error("Pairing needs scooter address or serial number")]
+  MissingKey,
+  #[error("Pairing file has unsupported format version: {0}")]
+  UnsupportedVersion(u32),
+  #[error("Pairing file is corrupted: {0}")]
+  Corrupted(serde_json::Error),
+  #[error("Could not access pairing file: {0}")]
+  Io(std::io::Error)
+}
+
+impl From<std::io::Error> for PairingError {
+  fn from(other: std::io::Error) -> Self {
+    PairingError::Io(other)
+  }
+}
+
+/**
+ * Everything that identifies scooter, that you can use to find pairing
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum PairingKey {
+  Address(BDAddr),
+  Serial(String)
+}
+
+/**
+ * Auth token received from RegistrationRequest with some details about scooter it belongs to
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pairing {
+  pub token: AuthToken,
+  pub address: Option<BDAddr>,
+  pub serial: Option<String>,
+  pub device_name: Option<String>,
+  pub firmware_version: Option<String>,
+  /**
+   * Seconds since unix epoch
+   */
+  pub registered_at: u64,
+}
+
+impl Pairing {
+  /**
+   * New pairing for scooter with address, registered right now
+   */
+  pub fn new(address: BDAddr, token: AuthToken) -> Self {
+    let registered_at = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs())
+      .unwrap_or(0);
+
+    Self {
+      token,
+      address: Some(address),
+      serial: None,
+      device_name: None,
+      firmware_version: None,
+      registered_at
+    }
+  }
+
+  pub fn matches(&self, key: &PairingKey) -> bool {
+    match key {
+      PairingKey::Address(address) => self.address.as_ref() == Some(address),
+      PairingKey::Serial(serial) => self.serial.as_ref() == Some(serial)
+    }
+  }
+
+  /**
+   * True if both pairings describe the same scooter
+   */
+  fn same_scooter(&self, other: &Pairing) -> bool {
+    let same_address = self.address.is_some() && self.address == other.address;
+    let same_serial = self.serial.is_some() && self.serial == other.serial;
+
+    same_address || same_serial
+  }
+}
+
+/**
+ * Keeps auth tokens for many scooters, so you can find right one by address or serial number
+ */
+#[async_trait]
+pub trait PairingStore: Send + Sync {
+  async fn get(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError>;
+
+  /**
+   * Save pairing, replacing older one for the same scooter
+   */
+  async fn save(&self, pairing: Pairing) -> Result<(), PairingError>;
+
+  async fn remove(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError>;
+
+  async fn list(&self) -> Result<Vec<Pairing>, PairingError>;
+}
+
+fn upsert(pairings: &mut Vec<Pairing>, pairing: Pairing) -> Result<(), PairingError> {
+  if pairing.address.is_none() && pairing.serial.is_none() {
+    return Err(PairingError::MissingKey)
+  }
+
+  pairings.retain(|existing| !existing.same_scooter(&pairing));
+  pairings.push(pairing);
+
+  Ok(())
+}
+
+fn take(pairings: &mut Vec<Pairing>, key: &PairingKey) -> Option<Pairing> {
+  let index = pairings.iter().position(|pairing| pairing.matches(key))?;
+  Some(pairings.remove(index))
+}
+
+/**
+ * Pairing store that forgets everything when dropped. Handy for tests and short lived tools
+ */
+#[derive(Clone, Default)]
+pub struct MemoryPairingStore {
+  pairings: Arc<RwLock<Vec<Pairing>>>
+}
+
+impl MemoryPairingStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+#[async_trait]
+impl PairingStore for MemoryPairingStore {
+  async fn get(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError> {
+    let pairings = self.pairings.read().await;
+    Ok(pairings.iter().find(|pairing| pairing.matches(key)).cloned())
+  }
+
+  async fn save(&self, pairing: Pairing) -> Result<(), PairingError> {
+    upsert(&mut *self.pairings.write().await, pairing)
+  }
+
+  async fn remove(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError> {
+    Ok(take(&mut *self.pairings.write().await, key))
+  }
+
+  async fn list(&self) -> Result<Vec<Pairing>, PairingError> {
+    Ok(self.pairings.read().await.clone())
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PairingFile {
+  version: u32,
+  pairings: Vec<Pairing>
+}
+
+/**
+ * Pairing store persisted as versioned json file. File is replaced atomically, so it is never left half written.
+ * Stores opened on the same file in one process share a lock, so they do not lose each other's updates
+ */
+#[derive(Clone)]
+pub struct FilePairingStore {
+  path: PathBuf,
+  lock: Arc<RwLock<()>>
+}
+
+impl FilePairingStore {
+  pub fn new<P: AsRef<Path>>(path: P) -> Self {
+    Self {
+      path: path.as_ref().to_path_buf(),
+      lock: file_lock(path.as_ref())
+    }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /**
+   * Import 12 byte auth token written to `.mi-token` by older versions of examples, as pairing for scooter with address.
+   * Returns None when there is no token file
+   */
+  pub async fn import_token_file<P: AsRef<Path>>(&self, path: P, address: BDAddr) -> Result<Option<Pairing>, PairingError> {
+    let mut file = match fs::File::open(path.as_ref()).await {
+      Ok(file) => file,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+      Err(error) => return Err(error.into())
+    };
+
+    let mut token : AuthToken = [0; 12];
+    file.read_exact(&mut token).await?;
+
+    let pairing = Pairing::new(address, token);
+    self.save(pairing.clone()).await?;
+
+    Ok(Some(pairing))
+  }
+
+  async fn load(&self) -> Result<Vec<Pairing>, PairingError> {
+    let bytes = match fs::read(&self.path).await {
+      Ok(bytes) => bytes,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+      Err(error) => return Err(error.into())
+    };
+
+    let version : serde_json::Value = serde_json::from_slice(&bytes)
+      .map_err(PairingError::Corrupted)?;
+    let version = version["version"].as_u64().unwrap_or(0) as u32;
+
+    if version != FORMAT_VERSION {
+      return Err(PairingError::UnsupportedVersion(version))
+    }
+
+    let file : PairingFile = serde_json::from_slice(&bytes)
+      .map_err(PairingError::Corrupted)?;
+
+    Ok(file.pairings)
+  }
+
+  async fn store(&self, pairings: Vec<Pairing>) -> Result<(), PairingError> {
+    let file = PairingFile { version: FORMAT_VERSION, pairings };
+    let bytes = serde_json::to_vec_pretty(&file)
+      .map_err(PairingError::Corrupted)?;
+
+    let mut tmp_path = self.path.clone().into_os_string();
+    tmp_path.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    tracing::debug!("Writing pairings to {:?}", tmp_path);
+    let written = async {
+      let mut tmp = fs::File::create(&tmp_path).await?;
+      tmp.write_all(&bytes).await?;
+      tmp.sync_all().await?;
+      drop(tmp);
+
+      fs::rename(&tmp_path, &self.path).await
+    }.await;
+
+    if written.is_err() {
+      let _ = fs::remove_file(&tmp_path).await;
+    }
+
+    Ok(written?)
+  }
+}
+
+#[async_trait]
+impl PairingStore for FilePairingStore {
+  async fn get(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError> {
+    let _guard = self.lock.read().await;
+    let pairings = self.load().await?;
+
+    Ok(pairings.into_iter().find(|pairing| pairing.matches(key)))
+  }
+
+  async fn save(&self, pairing: Pairing) -> Result<(), PairingError> {
+    let _guard = self.lock.write().await;
+    let mut pairings = self.load().await?;
+
+    upsert(&mut pairings, pairing)?;
+    self.store(pairings).await
+  }
+
+  async fn remove(&self, key: &PairingKey) -> Result<Option<Pairing>, PairingError> {
+    let _guard = self.lock.write().await;
+    let mut pairings = self.load().await?;
+
+    let removed = take(&mut pairings, key);
+    if removed.is_some() {
+      self.store(pairings).await?;
+    }
+
+    Ok(removed)
+  }
+
+  async fn list(&self) -> Result<Vec<Pairing>, PairingError> {
+    let _guard = self.lock.read().await;
+    self.load().await
+  }
+}
@@ -1,73 +1,101 @@
 use crate::consts::{MiCommands, Registers};
-use uuid::Uuid;
-use futures::Stream;
+use crate::transport::{ScooterTransport, NotificationStream, TransportError};
+use crate::mi_crypto::crc16;
 use futures::stream::StreamExt;
 use pretty_hex::*;
-use std::{pin::Pin, boxed::Box};
-use btleplug::platform::{Peripheral};
 use tokio::time::timeout;
+use std::collections::VecDeque;
+use std::sync::Mutex;
 use std::time::Duration;
-use btleplug::api::{Peripheral as _, Characteristic, WriteType, ValueNotification};
+use btleplug::api::ValueNotification;
 use anyhow::{Context, Result, anyhow};
+use thiserror::Error;
 
-const NB_CHUNK_SIZE : usize = 20;
-const MI_CHUNK_SIZE : usize = 18;
+pub(crate) const NB_CHUNK_SIZE : usize = 20;
+pub(crate) const MI_CHUNK_SIZE : usize = 18;
+pub(crate) const NB_HEADER : [u8; 2] = [0x55, 0xab];
+/**
+ * Bytes in ninebot frame that are not counted by length byte: header, length, message counter, random bytes, mac and crc
+ */
+pub(crate) const NB_FRAME_OVERHEAD : usize = 16;
+/**
+ * How many notifications for other characteristics are kept while waiting for specific one
+ */
+const MAX_PENDING_NOTIFICATIONS : usize = 32;
+
+#[derive(Error, Debug)]
+pub enum ProtocolError {
+  #[error("Ninebot frame has invalid checksum, expected: {expected:02x?}, received: {received:02x?}")]
+  InvalidChecksum { expected: [u8; 2], received: [u8; 2] }
+}
 
 /**
  * This structs hides all bluetooth shenanigans under easy to use commands.
  */
-pub struct MiProtocol {
-  device: Peripheral,
-  avdtp: Characteristic,
-  upnp: Characteristic,
-  tx: Characteristic,
-  rx: Characteristic,
-  stream: Pin<Box<dyn Stream<Item = ValueNotification>>>,
+pub struct MiProtocol<T: ScooterTransport> {
+  transport: T,
+  /**
+   * Only read through `&mut self`, mutex just makes protocol Sync so write futures can be sent between threads
+   */
+  stream: Mutex<NotificationStream>,
+  /**
+   * Notifications received while waiting for other characteristic
+   */
+  pending: VecDeque<ValueNotification>,
 }
 
-impl MiProtocol {
-  pub async fn new(device: &Peripheral) -> Result<Self> {
-    let (avdtp, upnp, tx, rx) = setup_channels(&device).await?;
-    let stream : Pin<Box<dyn Stream<Item = ValueNotification>>> = device.notifications().await
-      .with_context(|| format!("Could not load notifications stream"))?;
-    let device = device.clone();
-
-    let instance = Self {
-      device,
-      stream,
-      avdtp,
-      upnp,
-      tx,
-      rx
-    };
+impl<T: ScooterTransport> MiProtocol<T> {
+  pub async fn new(transport: &T) -> Result<Self> {
+    let stream = transport.notifications().await?;
+    let transport = transport.clone();
 
-    Ok(instance)
+    Ok(Self { transport, stream: Mutex::new(stream), pending: VecDeque::new() })
   }
 
   pub async fn dispose(&self) -> Result<bool> {
-    self.device.unsubscribe(&self.avdtp).await?;
-    self.device.unsubscribe(&self.upnp).await?;
-    self.device.unsubscribe(&self.rx).await?;
+    self.transport.unsubscribe().await?;
 
     Ok(true)
   }
 
-  fn reg_to_channel(&self, reg : &Registers) -> Option<&Characteristic> {
-    match reg {
-      Registers::RX => Some(&self.rx),
-      Registers::TX => Some(&self.tx),
-      Registers::AVDTP => Some(&self.avdtp),
-      Registers::UPNP => Some(&self.upnp),
-      _ => None
-    }
-  }
-
   /**
    * Read next notification
    */
   pub async fn next(&mut self) -> Option<ValueNotification> {
+    if let Some(notification) = self.pending.pop_front() {
+      return Some(notification)
+    }
+
     tracing::debug!("Waiting for notifications...");
-    self.stream.next().await
+    self.stream.get_mut().unwrap().next().await
+  }
+
+  /**
+   * Read next notification from specified characteristic. Notifications from other characteristics are kept for later reads
+   */
+  pub async fn next_on(&mut self, reg: &Registers) -> Option<ValueNotification> {
+    let uuid = reg.to_uuid();
+
+    if let Some(index) = self.pending.iter().position(|notification| notification.uuid == uuid) {
+      return self.pending.remove(index)
+    }
+
+    tracing::debug!("Waiting for notifications on {:?}...", reg);
+    while let Some(notification) = self.stream.get_mut().unwrap().next().await {
+      if notification.uuid == uuid {
+        return Some(notification)
+      }
+
+      tracing::debug!("Keeping notification from {} for later: {:?}", notification.uuid, notification.value.hex_dump());
+      if self.pending.len() >= MAX_PENDING_NOTIFICATIONS {
+        if let Some(dropped) = self.pending.pop_front() {
+          tracing::warn!("Dropping unread notification from {}: {:?}", dropped.uuid, dropped.value.hex_dump());
+        }
+      }
+      self.pending.push_back(notification);
+    }
+
+    None
   }
 
   pub async fn wait_for_scooter_to_receive_data(&mut self) -> Result<bool> {
@@ -112,7 +140,20 @@ impl MiProtocol {
       return Ok(notification)
     }
 
-    Err(anyhow!("Received empty message from mi scooter..."))
+    Err(TransportError::StreamClosed.into())
+  }
+
+  /**
+   * Try to read next notification from specified characteristic, If nothing comes in specified duration throw error
+   */
+  pub async fn wait_for_notification_on(&mut self, reg: &Registers, duration : Duration) -> Result<ValueNotification> {
+    let response = timeout(duration, self.next_on(reg)).await?;
+
+    if let Some(notification) = response {
+      return Ok(notification)
+    }
+
+    Err(TransportError::StreamClosed.into())
   }
 
   /**
@@ -126,30 +167,50 @@ impl MiProtocol {
    * Send mi command to register on scooter
    */
   pub async fn write(&self, reg: &Registers, command: MiCommands) -> Result<bool> {
-    let channel = self.reg_to_channel(reg).unwrap();
     tracing::debug!("-> {:?} -> {:?}", command, &reg);
 
-    self.device.write(&channel, &command.to_bytes(), WriteType::WithoutResponse).await
+    self.transport.write(reg, &command.to_bytes()).await
       .with_context(|| format!("Could not write command: {:?} to {:?}", command, &reg))?;
 
     Ok(true)
   }
 
   /**
-   * Ninebot protocol sends multiple messages. I don't know how long they will be, but this is persistent per command, so you can specify it as arg
+   * Read single ninebot frame from RX, that can be split into multiple notifications. Length of frame is taken from its header,
+   * notifications that do not start new frame are skipped. Frame is returned with header and checksum after checksum is verified.
    */
-  pub async fn read_nb_parcel(&mut self, frames: u8) -> Result<Vec<u8>> {
-    let mut buffer : Vec<u8> = Vec::new();
-    let mut frames_left = frames;
+  pub async fn read_nb_parcel(&mut self) -> Result<Vec<u8>> {
     let duration = Duration::from_secs(5);
 
-    tracing::debug!("Reading nb frames: {}", frames_left);
-    while frames_left > 0 {
-      tracing::debug!("  Reading frame...");
-      let notification = self.wait_for_notification_with_timeout(duration).await?;
+    let mut buffer = loop {
+      let notification = self.wait_for_notification_on(&Registers::RX, duration).await?;
+      tracing::debug!("  Received data: {:?}", notification.value.hex_dump());
+
+      if notification.value.len() > 2 && notification.value[0..2] == NB_HEADER {
+        break notification.value
+      }
+
+      tracing::warn!("Skipping notification that does not start ninebot frame: {:?}", notification.value.hex_dump());
+    };
+
+    let frame_size = buffer[2] as usize + NB_FRAME_OVERHEAD;
+    tracing::debug!("Reading nb frame with {} bytes", frame_size);
+
+    while buffer.len() < frame_size {
+      let notification = self.wait_for_notification_on(&Registers::RX, duration).await?;
       tracing::debug!("  Received data: {:?}", notification.value.hex_dump());
       buffer.extend_from_slice(notification.value.as_slice());
-      frames_left -= 1;
+    }
+
+    if buffer.len() > frame_size {
+      tracing::warn!("Dropping {} bytes received after end of frame", buffer.len() - frame_size);
+      buffer.truncate(frame_size);
+    }
+
+    let expected = crc16(&buffer[2..frame_size - 2]);
+    let received = [buffer[frame_size - 2], buffer[frame_size - 1]];
+    if expected != received {
+      return Err(ProtocolError::InvalidChecksum { expected, received }.into())
     }
 
     tracing::debug!("  Finished reading: {:?}", buffer.hex_dump());
@@ -165,20 +226,18 @@ impl MiProtocol {
     let mut total_frames : u16 = 0;
     let mut received_data : Vec<u8> = Vec::new();
 
-    if let Some(data) = self.stream.next().await {
+    if let Some(data) = self.next().await {
       total_frames = data.value[4] as u16 + 0x100 * data.value[5] as u16;
       tracing::debug!("Expecting {} frames: {:?}", total_frames, data.value.hex_dump());
 
       self.write(reg, MiCommands::RCV_RDY).await?;
     }
 
-    while let Some(data) = self.stream.next().await {
+    while let Some(data) = self.next().await {
       let current_frame : u16 = what_frame(&data.value);
       tracing::debug!("Current frame {}: {:?}", current_frame, data.value.hex_dump());
 
-      for i in 2..data.value.len() {
-        received_data.push(data.value[i]);
-      }
+      received_data.extend_from_slice(&data.value[2..]);
 
       if current_frame == total_frames {
         break;
@@ -192,12 +251,10 @@ impl MiProtocol {
   }
 
   pub async fn write_nb_parcel(&self, reg: &Registers, data: &[u8]) -> Result<bool> {
-    let channel = self.reg_to_channel(reg).unwrap();
-
     for chunk in data.chunks(NB_CHUNK_SIZE) {
       tracing::debug!("Writing nb chunk to {:?}: {:?}", reg, chunk.hex_dump());
-      self.device.write(&channel, &chunk, WriteType::WithoutResponse).await
-        .with_context(|| format!("Could not write mi chunk: for channel: {:?}", channel))?;
+      self.transport.write(reg, chunk).await
+        .with_context(|| format!("Could not write nb chunk to {:?}", reg))?;
     }
 
     Ok(true)
@@ -208,72 +265,23 @@ impl MiProtocol {
    */
   pub async fn write_mi_parcel(&self, reg: &Registers, data: &[u8]) -> Result<bool> {
     let mut buffer : Vec<u8> = Vec::new();
-    let mut chunk_index = 1;
-    let channel = self.reg_to_channel(reg).unwrap();
 
-    for chunk in data.chunks(MI_CHUNK_SIZE) {
+    for (index, chunk) in data.chunks(MI_CHUNK_SIZE).enumerate() {
+      let chunk_index = index as u8 + 1;
       buffer.clear();
       buffer.push(chunk_index);
       buffer.push(0);
-
-      for byte in chunk { // There should be better way of doing this...
-        buffer.push(*byte);
-      }
+      buffer.extend_from_slice(chunk);
 
       tracing::debug!("Writing mi chunk {} to {:?}: {:?}", chunk_index, reg, buffer.hex_dump());
-      self.device.write(&channel, &buffer, WriteType::WithoutResponse).await
-        .with_context(|| format!("Could not write mi chunk: {} for channel: {:?}", chunk_index, channel))?;
-      chunk_index += 1;
+      self.transport.write(reg, &buffer).await
+        .with_context(|| format!("Could not write mi chunk: {} to {:?}", chunk_index, reg))?;
     }
 
     Ok(true)
   }
 }
 
-async fn find_characteristic(device : &Peripheral, service_uuid: Uuid, char_uuid: Uuid) -> Result<Characteristic> {
-  device.discover_services().await
-    .with_context(|| format!("Could not enable discovering devices"))?;
-
-  for ch in device.characteristics() {
-    if ch.uuid == char_uuid && ch.service_uuid == service_uuid {
-      tracing::debug!("Found Characteristic: {:?}", ch);
-      return Ok(ch)
-    } else {
-      tracing::debug!("Skipped Characteristic: {:?}", ch);
-    }
-  }
-
-  Err(anyhow!("Could not find characteristic: {}", char_uuid))
-}
-
-fn what_frame(bytes: &Vec<u8>) -> u16 {
-  bytes[0] as u16 & 0xff + 0x100 * bytes[1] as u16 & 0xff
-}
-
-async fn setup_channels(device : &Peripheral) -> Result<(Characteristic, Characteristic, Characteristic, Characteristic)> {
-  device.discover_services().await?;
-
-  // Auth channels
-  tracing::debug!("Setting up AUTH channels");
-  let avdtp = find_characteristic(device, Registers::AUTH.to_uuid(), Registers::AVDTP.to_uuid()).await?;
-  let upnp = find_characteristic(device, Registers::AUTH.to_uuid(), Registers::UPNP.to_uuid()).await?;
-
-  // UART channels
-  tracing::debug!("Setting up UART channels");
-  let tx = find_characteristic(device, Registers::UART.to_uuid(), Registers::TX.to_uuid()).await?;
-  let rx = find_characteristic(device, Registers::UART.to_uuid(), Registers::RX.to_uuid()).await?;
-
-  tracing::debug!("Enabling notify for AVDTP");
-  device.subscribe(&avdtp).await
-    .with_context(|| format!("Could not subscribe to scooter AVDTP notifications"))?;
-
-  tracing::debug!("Enabling notify for UPNP");
-  device.subscribe(&upnp).await
-    .with_context(|| format!("Could not subscribe to scooter UPNP notifications"))?;
-
-  tracing::debug!("Enabling notify for RX");
-  device.subscribe(&rx).await
-    .with_context(|| format!("Could not subscribe to scooter RX notifications"))?;
-
-  Ok((avdtp, upnp, tx, rx))
+fn what_frame(bytes: &[u8]) -> u16 {
+  bytes[0] as u16 + 0x100 * bytes[1] as u16
 }
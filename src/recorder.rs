@@ -0,0 +1,360 @@
+use crate::session::{MiSession, ScooterModel, FirmwareVersions, TelemetrySample};
+use crate::transport::ScooterTransport;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+/**
+ * Bump when layout of header or samples changes
+ */
+const FORMAT_VERSION : u32 = 1;
+
+/**
+ * First line of csv ride starts with this, so reader can tell csv and json apart
+ */
+const CSV_HEADER_PREFIX : &str = "# ";
+
+#[derive(Error, Debug)]
+pub enum RideError {
+  #[error("Ride file is empty")]
+  Empty,
+  #[error("Ride file has unsupported format version: {0}")]
+  UnsupportedVersion(u32),
+  #[error("Ride file is corrupted at line {line}: {reason}")]
+  Corrupted { line: usize, reason: String },
+  #[error("Could not write ride: {0}")]
+  Write(String),
+  #[error("Could not access ride file: {0}")]
+  Io(std::io::Error)
+}
+
+impl From<std::io::Error> for RideError {
+  fn from(other: std::io::Error) -> Self {
+    RideError::Io(other)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RideFormat {
+  /**
+   * Header and every sample as json object on separate line
+   */
+  Ndjson,
+  /**
+   * Header as json in comment on first line, then table with one sample per row
+   */
+  Csv
+}
+
+impl RideFormat {
+  /**
+   * Pick format by file extension, everything that is not .csv is written as ndjson
+   */
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+    match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+      Some("csv") => RideFormat::Csv,
+      _ => RideFormat::Ndjson
+    }
+  }
+}
+
+/**
+ * Which scooter recorded ride and where it started
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RideHeader {
+  pub version: u32,
+  pub serial: String,
+  pub model: ScooterModel,
+  pub firmware: FirmwareVersions,
+  /**
+   * Total distance of scooter when recording started, in meters
+   */
+  pub start_odometer_m: u32,
+  /**
+   * Milliseconds since unix epoch
+   */
+  pub started_at_ms: u64
+}
+
+impl RideHeader {
+  pub fn new(serial: String, model: ScooterModel, firmware: FirmwareVersions, start_odometer_m: u32) -> Self {
+    Self {
+      version: FORMAT_VERSION,
+      serial,
+      model,
+      firmware,
+      start_odometer_m,
+      started_at_ms: unix_millis(SystemTime::now())
+    }
+  }
+}
+
+/**
+ * Single telemetry sample flattened for storage. Values of groups that were not polled yet are None
+ */
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RideSample {
+  /**
+   * Milliseconds since unix epoch
+   */
+  pub timestamp_ms: u64,
+  pub speed_kmh: Option<f32>,
+  pub speed_average_kmh: Option<f32>,
+  pub total_distance_m: Option<u32>,
+  pub frame_temperature: Option<f32>,
+  /**
+   * Number shown on dashboard, 0 when there is no error
+   */
+  pub error_code: Option<u16>,
+  pub trip_duration_s: Option<u64>,
+  pub trip_distance_m: Option<u16>,
+  pub distance_left_km: Option<f32>,
+  pub battery_percent: Option<u16>,
+  pub battery_capacity: Option<u16>,
+  pub battery_voltage: Option<f32>,
+  pub battery_current: Option<f32>,
+  #[serde(with = "cell_voltages")]
+  pub cell_voltages: Vec<f32>
+}
+
+impl From<&TelemetrySample> for RideSample {
+  fn from(sample: &TelemetrySample) -> Self {
+    let motor = sample.motor.as_ref();
+    let trip = sample.trip.as_ref();
+    let battery = sample.battery.as_ref();
+
+    Self {
+      timestamp_ms: unix_millis(sample.timestamp),
+      speed_kmh: motor.map(|motor| motor.speed_kmh),
+      speed_average_kmh: motor.map(|motor| motor.speed_average_kmh),
+      total_distance_m: motor.map(|motor| motor.total_distance_m),
+      frame_temperature: motor.map(|motor| motor.frame_temperature),
+      error_code: motor.map(|motor| motor.error.code()),
+      trip_duration_s: trip.map(|trip| trip.duration.as_secs()),
+      trip_distance_m: trip.map(|trip| trip.distance_m),
+      distance_left_km: sample.distance_left_km,
+      battery_percent: battery.map(|battery| battery.percent),
+      battery_capacity: battery.map(|battery| battery.capacity),
+      battery_voltage: battery.map(|battery| battery.voltage),
+      battery_current: battery.map(|battery| battery.current),
+      cell_voltages: sample.cell_voltages.clone().unwrap_or_default()
+    }
+  }
+}
+
+/**
+ * Csv can't hold list in one column, so cell voltages are stored joined with ; in both formats
+ */
+mod cell_voltages {
+  use serde::{Serializer, Deserializer, Deserialize, de::Error};
+
+  pub fn serialize<S: Serializer>(voltages: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    let voltages : Vec<String> = voltages.iter().map(|voltage| voltage.to_string()).collect();
+    serializer.serialize_str(&voltages.join(";"))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    String::deserialize(deserializer)?
+      .split(';')
+      .filter(|voltage| !voltage.is_empty())
+      .map(|voltage| voltage.parse().map_err(D::Error::custom))
+      .collect()
+  }
+}
+
+enum Output<W: Write> {
+  Ndjson(W),
+  Csv(Box<csv::Writer<W>>)
+}
+
+/**
+ * Writes header and then every sample as soon as it is recorded, so ride is not lost when app is killed.
+ *
+ * ```no_run
+ * # async fn run(mut session: m365::MiSession) -> anyhow::Result<()> {
+ * use futures::StreamExt;
+ * use m365::{RideRecorder, TelemetryConfig};
+ *
+ * let header = session.ride_header().await?;
+ * let mut recorder = RideRecorder::create("ride.csv", &header)?;
+ *
+ * let mut telemetry = Box::pin(session.telemetry_stream(TelemetryConfig::default())?);
+ * while let Some(Ok(sample)) = telemetry.next().await {
+ *   recorder.record(&sample)?;
+ * }
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct RideRecorder<W: Write> {
+  output: Output<W>
+}
+
+impl RideRecorder<BufWriter<File>> {
+  /**
+   * Create file, format is picked by extension
+   */
+  pub fn create<P: AsRef<Path>>(path: P, header: &RideHeader) -> Result<Self, RideError> {
+    let format = RideFormat::from_path(&path);
+    let file = BufWriter::new(File::create(path)?);
+
+    Self::new(file, format, header)
+  }
+}
+
+impl<W: Write> RideRecorder<W> {
+  pub fn new(mut writer: W, format: RideFormat, header: &RideHeader) -> Result<Self, RideError> {
+    let header = serde_json::to_string(header).map_err(|error| RideError::Write(error.to_string()))?;
+
+    let output = match format {
+      RideFormat::Ndjson => {
+        writeln!(writer, "{}", header)?;
+        Output::Ndjson(writer)
+      },
+      RideFormat::Csv => {
+        writeln!(writer, "{}{}", CSV_HEADER_PREFIX, header)?;
+        Output::Csv(Box::new(csv::Writer::from_writer(writer)))
+      }
+    };
+
+    Ok(Self { output })
+  }
+
+  pub fn record(&mut self, sample: &TelemetrySample) -> Result<(), RideError> {
+    self.record_sample(&RideSample::from(sample))
+  }
+
+  pub fn record_sample(&mut self, sample: &RideSample) -> Result<(), RideError> {
+    match &mut self.output {
+      Output::Ndjson(writer) => {
+        serde_json::to_writer(&mut *writer, sample).map_err(|error| RideError::Write(error.to_string()))?;
+        writeln!(writer)?;
+        writer.flush()?;
+      },
+      Output::Csv(writer) => {
+        writer.serialize(sample).map_err(|error| RideError::Write(error.to_string()))?;
+        writer.flush()?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /**
+   * Flush and return underlying writer
+   */
+  pub fn finish(self) -> Result<W, RideError> {
+    match self.output {
+      Output::Ndjson(mut writer) => {
+        writer.flush()?;
+        Ok(writer)
+      },
+      Output::Csv(writer) => (*writer).into_inner().map_err(|error| RideError::Io(error.into_error()))
+    }
+  }
+}
+
+/**
+ * Recorded ride, format is detected from first line
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ride {
+  pub format: RideFormat,
+  pub header: RideHeader,
+  pub samples: Vec<RideSample>
+}
+
+impl Ride {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, RideError> {
+    Self::read(BufReader::new(File::open(path)?))
+  }
+
+  pub fn read<R: BufRead>(mut reader: R) -> Result<Self, RideError> {
+    let mut first_line = String::new();
+    if reader.read_line(&mut first_line)? == 0 {
+      return Err(RideError::Empty)
+    }
+
+    let (format, header) = match first_line.strip_prefix(CSV_HEADER_PREFIX) {
+      Some(header) => (RideFormat::Csv, header),
+      None => (RideFormat::Ndjson, first_line.as_str())
+    };
+
+    let version : serde_json::Value = serde_json::from_str(header).map_err(|error| corrupted(1, error))?;
+    let version = version["version"].as_u64().unwrap_or(0) as u32;
+    if version != FORMAT_VERSION {
+      return Err(RideError::UnsupportedVersion(version))
+    }
+
+    let header : RideHeader = serde_json::from_str(header).map_err(|error| corrupted(1, error))?;
+
+    let samples = match format {
+      RideFormat::Ndjson => read_ndjson(reader)?,
+      RideFormat::Csv => read_csv(reader)?
+    };
+
+    Ok(Self { format, header, samples })
+  }
+
+  /**
+   * Distance in meters between odometer at start and last sample
+   */
+  pub fn distance_m(&self) -> u32 {
+    self.samples.iter()
+      .rev()
+      .find_map(|sample| sample.total_distance_m)
+      .map(|odometer| odometer.saturating_sub(self.header.start_odometer_m))
+      .unwrap_or(0)
+  }
+}
+
+fn read_ndjson<R: BufRead>(reader: R) -> Result<Vec<RideSample>, RideError> {
+  let mut samples = Vec::new();
+
+  for (index, line) in reader.lines().enumerate() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue
+    }
+
+    samples.push(serde_json::from_str(&line).map_err(|error| corrupted(index + 2, error))?);
+  }
+
+  Ok(samples)
+}
+
+fn read_csv<R: BufRead>(reader: R) -> Result<Vec<RideSample>, RideError> {
+  let mut samples = Vec::new();
+
+  for (index, sample) in csv::Reader::from_reader(reader).deserialize().enumerate() {
+    samples.push(sample.map_err(|error| corrupted(index + 3, error))?);
+  }
+
+  Ok(samples)
+}
+
+fn corrupted<E: ToString>(line: usize, error: E) -> RideError {
+  RideError::Corrupted { line, reason: error.to_string() }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+  time.duration_since(UNIX_EPOCH).map(|duration| duration.as_millis() as u64).unwrap_or(0)
+}
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Read serial number, firmware and odometer for header of new ride
+   */
+  pub async fn ride_header(&mut self) -> anyhow::Result<RideHeader> {
+    let serial = self.serial_number().await?;
+    let firmware = self.firmware_versions().await?;
+    let start_odometer_m = self.motor_info().await?.total_distance_m;
+
+    Ok(RideHeader::new(serial, self.model(), firmware, start_odometer_m))
+  }
+}
@@ -1,6 +1,7 @@
 use crate::consts::{MiCommands, Registers};
 pub use crate::mi_crypto::AuthToken;
 use crate::protocol::MiProtocol;
+use crate::transport::ScooterTransport;
 use crate::mi_crypto;
 
 use pretty_hex::*;
@@ -25,20 +26,20 @@ impl From<anyhow::Error> for RegistrationError {
   }
 }
 
-pub struct RegistrationRequest {
-  protocol: MiProtocol,
+pub struct RegistrationRequest<T: ScooterTransport = Peripheral> {
+  protocol: MiProtocol<T>,
   my_secret_key: EphemeralSecret,
   my_public_key: PublicKey,
   remote_info: Option<Vec<u8>>,
   token: Option<AuthToken>
 }
 
-impl RegistrationRequest {
+impl<T: ScooterTransport> RegistrationRequest<T> {
   /**
    * Create new registration request for device. It is important that device is a M365 scooter, and you did already connect to it
    */
-  pub async fn new(device : &Peripheral) -> Result<Self> {
-    let protocol = MiProtocol::new(device).await?;
+  pub async fn new(transport : &T) -> Result<Self> {
+    let protocol = MiProtocol::new(transport).await?;
 
     let (my_secret_key, my_public_key) = mi_crypto::gen_key_pair();
     tracing::debug!("Public key: {:?}", my_public_key);
@@ -125,7 +126,7 @@ impl RegistrationRequest {
     let remote_key_bytes = self.protocol.read_mi_parcel(&Registers::AVDTP).await?;
     let remote_info = self.remote_info.as_ref().unwrap();
     let remote_key_bytes = [&[0x04], remote_key_bytes.as_slice()].concat();
-    let (did_ct, token) = mi_crypto::calc_did(&self.my_secret_key, &remote_key_bytes, &remote_info);
+    let (did_ct, token) = mi_crypto::calc_did(&self.my_secret_key, &remote_key_bytes, remote_info);
 
     self.token = Some(token);
     self.protocol.write(&Registers::AVDTP, MiCommands::CMD_SEND_DID).await?;
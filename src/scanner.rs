@@ -1,4 +1,4 @@
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use anyhow::Result;
 use tokio::sync::mpsc;
 use std::collections::HashSet;
@@ -45,7 +45,7 @@ pub enum ScannerEvent {
   DiscoveredScooter(TrackedDevice)
 }
 
-#[derive(Clone, Debug, Hash, Eq)]
+#[derive(Clone, Debug, Eq)]
 pub struct TrackedDevice {
   pub id: PeripheralId,
   pub addr: BDAddr,
@@ -60,7 +60,7 @@ impl TrackedDevice {
     if let Some(name) = &self.name {
       return name.starts_with(XIAOMI_SCOOTER_NAME);
     }
-    return false;
+    false
   }
 }
 
@@ -70,6 +70,12 @@ impl PartialEq for TrackedDevice {
   }
 }
 
+impl Hash for TrackedDevice {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.addr.hash(state);
+  }
+}
+
 /**
  * Use scooter scanner to find scooter.
  * By default all Xiaomi scooter names start with MIScooter and then have few digits after name.
@@ -149,7 +155,7 @@ impl ScooterScanner {
       .await
       .iter()
       .filter(|tracked_device| tracked_device.is_scooter())
-      .map(|tracked_device| tracked_device.clone())
+      .cloned()
       .collect::<Vec<TrackedDevice>>()
   }
 
@@ -161,7 +167,7 @@ impl ScooterScanner {
       .read()
       .await
       .iter()
-      .map(|tracked_device| tracked_device.clone())
+      .cloned()
       .collect::<Vec<TrackedDevice>>()
   }
 }
@@ -185,15 +191,12 @@ impl CentralEventsProcessor {
     let mut events = self.central.events().await?;
 
     while let Some(event) = events.next().await {
-      match event {
-        CentralEvent::DeviceDiscovered(peer_id) => {
-          if let Some(tracked_device) = self.track_device(&peer_id).await? {
-            if tracked_device.is_scooter() {
-              self.tx.send(ScannerEvent::DiscoveredScooter(tracked_device)).await?;
-            }
+      if let CentralEvent::DeviceDiscovered(peer_id) = event {
+        if let Some(tracked_device) = self.track_device(&peer_id).await? {
+          if tracked_device.is_scooter() {
+            self.tx.send(ScannerEvent::DiscoveredScooter(tracked_device)).await?;
           }
-        },
-        _ => {}
+        }
       }
     }
     Ok(())
@@ -231,7 +234,7 @@ impl CentralEventsProcessor {
 async fn find_central(manager: &Manager) -> Result<Adapter, ScannerError> {
   let adapters = manager.adapters().await?;
 
-  if let Some(adapter) = adapters.into_iter().nth(0) {
+  if let Some(adapter) = adapters.into_iter().next() {
     Ok(adapter)
   } else {
     Err(ScannerError::MissingCentral)
@@ -0,0 +1,359 @@
+use crate::mi_crypto::AuthToken;
+use crate::pairing::{PairingStore, PairingKey};
+use crate::scanner::{ScooterScanner, TrackedDevice};
+use crate::connection::ConnectionHelper;
+use crate::login::LoginRequest;
+use crate::session::{MiSession, ScooterModel};
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use futures::stream::StreamExt;
+use btleplug::api::{BDAddr, Central, CentralEvent, Peripheral as _};
+use btleplug::platform::{Adapter, Peripheral, PeripheralId};
+use tokio::task::JoinHandle;
+use tokio::time::{self, timeout};
+use thiserror::Error;
+
+/**
+ * Steps needed to get ready session with scooter
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectStage {
+  Token,
+  Scan,
+  Connect,
+  Login
+}
+
+impl fmt::Display for ConnectStage {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConnectStage::Token => write!(fmt, "loading auth token"),
+      ConnectStage::Scan => write!(fmt, "scanning"),
+      ConnectStage::Connect => write!(fmt, "connecting"),
+      ConnectStage::Login => write!(fmt, "logging in")
+    }
+  }
+}
+
+#[derive(Error, Debug)]
+pub enum ScooterError {
+  #[error("Scooter {0} is not paired, register it first")]
+  NotPaired(BDAddr),
+  #[error("Timed out while {0}")]
+  Timeout(ConnectStage),
+  #[error("Failed while {stage}: {source}")]
+  Failed {
+    stage: ConnectStage,
+    source: anyhow::Error
+  }
+}
+
+impl ScooterError {
+  /**
+   * Stage that failed
+   */
+  pub fn stage(&self) -> ConnectStage {
+    match self {
+      ScooterError::NotPaired(_) => ConnectStage::Token,
+      ScooterError::Timeout(stage) => *stage,
+      ScooterError::Failed { stage, .. } => *stage
+    }
+  }
+
+  fn failed<E: Into<anyhow::Error>>(stage: ConnectStage, source: E) -> Self {
+    ScooterError::Failed { stage, source: source.into() }
+  }
+}
+
+/**
+ * Where to take auth token from
+ */
+#[derive(Clone)]
+pub enum TokenSource {
+  Token(AuthToken),
+  Store(Arc<dyn PairingStore>)
+}
+
+impl From<AuthToken> for TokenSource {
+  fn from(token: AuthToken) -> Self {
+    TokenSource::Token(token)
+  }
+}
+
+impl<S: PairingStore + 'static> From<Arc<S>> for TokenSource {
+  fn from(store: Arc<S>) -> Self {
+    TokenSource::Store(store)
+  }
+}
+
+impl TokenSource {
+  pub async fn token_for(&self, addr: &BDAddr) -> Result<AuthToken, ScooterError> {
+    match self {
+      TokenSource::Token(token) => Ok(*token),
+      TokenSource::Store(store) => {
+        let pairing = store.get(&PairingKey::Address(*addr)).await
+          .map_err(|error| ScooterError::failed(ConnectStage::Token, error))?;
+
+        pairing
+          .map(|pairing| pairing.token)
+          .ok_or(ScooterError::NotPaired(*addr))
+      }
+    }
+  }
+}
+
+/**
+ * Entry point for talking with scooter. It scans for scooter, connects, logs in and gives you ready to use session:
+ *
+ * ```no_run
+ * # async fn run(mac: btleplug::api::BDAddr, token: m365::AuthToken) -> anyhow::Result<()> {
+ * let (scooter, mut session) = m365::Scooter::connect(mac, token)
+ *   .retries(5)
+ *   .start()
+ *   .await?;
+ *
+ * println!("{:?}", session.motor_info().await?);
+ * scooter.disconnect().await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct Scooter {
+  scanner: ScooterScanner,
+  tracked_device: TrackedDevice,
+  device: Peripheral,
+  connection: ConnectionHelper,
+  token: AuthToken,
+  connected: Arc<AtomicBool>,
+  /**
+   * Background task following adapter events, stopped when scooter is dropped
+   */
+  watcher: JoinHandle<()>,
+  model: ScooterModel,
+  connect_timeout: Duration,
+  login_timeout: Duration,
+}
+
+impl Scooter {
+  pub fn connect<S: Into<TokenSource>>(addr: BDAddr, token_source: S) -> ScooterConnector {
+    ScooterConnector::new(addr, token_source.into())
+  }
+
+  pub fn address(&self) -> BDAddr {
+    self.tracked_device.addr
+  }
+
+  pub fn name(&self) -> Option<&str> {
+    self.tracked_device.name.as_deref()
+  }
+
+  pub fn token(&self) -> &AuthToken {
+    &self.token
+  }
+
+  /**
+   * Model detected after first login
+   */
+  pub fn model(&self) -> ScooterModel {
+    self.model
+  }
+
+  pub fn peripheral(&self) -> &Peripheral {
+    &self.device
+  }
+
+  pub fn scanner(&self) -> &ScooterScanner {
+    &self.scanner
+  }
+
+  /**
+   * False after adapter reported that scooter disconnected
+   */
+  pub fn is_connected(&self) -> bool {
+    self.connected.load(Ordering::SeqCst)
+  }
+
+  /**
+   * Drop current bluetooth connection, connect again and login. Old session can't be used after this
+   */
+  pub async fn reconnect(&self) -> Result<MiSession, ScooterError> {
+    timeout(self.connect_timeout, self.connection.reconnect()).await
+      .map_err(|_| ScooterError::Timeout(ConnectStage::Connect))?
+      .map_err(|error| ScooterError::failed(ConnectStage::Connect, error))?;
+
+    let mut session = timeout(self.login_timeout, login(&self.device, &self.token)).await
+      .map_err(|_| ScooterError::Timeout(ConnectStage::Login))??;
+    session.set_model(self.model);
+
+    self.connected.store(true, Ordering::SeqCst);
+    Ok(session)
+  }
+
+  pub async fn disconnect(&self) -> anyhow::Result<bool> {
+    self.connection.disconnect().await
+  }
+}
+
+impl Drop for Scooter {
+  fn drop(&mut self) {
+    self.watcher.abort();
+  }
+}
+
+async fn login(device: &Peripheral, token: &AuthToken) -> Result<MiSession, ScooterError> {
+  let mut request = LoginRequest::new(device, token).await
+    .map_err(|error| ScooterError::failed(ConnectStage::Login, error))?;
+
+  request.start().await
+    .map_err(|error| ScooterError::failed(ConnectStage::Login, error))
+}
+
+/**
+ * Follow adapter events in background and keep track if scooter is still connected
+ */
+async fn watch_connection(central: Adapter, id: PeripheralId, connected: Arc<AtomicBool>) -> anyhow::Result<JoinHandle<()>> {
+  let mut events = central.events().await?;
+
+  let watcher = tokio::spawn(async move {
+    while let Some(event) = events.next().await {
+      match event {
+        CentralEvent::DeviceDisconnected(peer_id) if peer_id == id => {
+          tracing::warn!("Scooter disconnected");
+          connected.store(false, Ordering::SeqCst);
+        },
+        CentralEvent::DeviceConnected(peer_id) if peer_id == id => {
+          tracing::debug!("Scooter connected");
+          connected.store(true, Ordering::SeqCst);
+        },
+        _ => {}
+      }
+    }
+  });
+
+  Ok(watcher)
+}
+
+/**
+ * Builder returned by Scooter::connect, use it to tune timeouts and retries before starting
+ */
+pub struct ScooterConnector {
+  addr: BDAddr,
+  token_source: TokenSource,
+  scan_timeout: Duration,
+  connect_timeout: Duration,
+  login_timeout: Duration,
+  retries: u32,
+  retry_delay: Duration,
+}
+
+impl ScooterConnector {
+  fn new(addr: BDAddr, token_source: TokenSource) -> Self {
+    Self {
+      addr,
+      token_source,
+      scan_timeout: Duration::from_secs(30),
+      connect_timeout: Duration::from_secs(30),
+      login_timeout: Duration::from_secs(15),
+      retries: 3,
+      retry_delay: Duration::from_secs(1),
+    }
+  }
+
+  /**
+   * How long to wait for scooter to show up, default is 30 seconds
+   */
+  pub fn scan_timeout(mut self, duration: Duration) -> Self {
+    self.scan_timeout = duration;
+    self
+  }
+
+  /**
+   * How long single connection attempt can take, default is 30 seconds
+   */
+  pub fn connect_timeout(mut self, duration: Duration) -> Self {
+    self.connect_timeout = duration;
+    self
+  }
+
+  /**
+   * How long single login attempt can take, default is 15 seconds
+   */
+  pub fn login_timeout(mut self, duration: Duration) -> Self {
+    self.login_timeout = duration;
+    self
+  }
+
+  /**
+   * How many times connecting and login is retried after first failure, default is 3
+   */
+  pub fn retries(mut self, retries: u32) -> Self {
+    self.retries = retries;
+    self
+  }
+
+  /**
+   * Pause between retries, default is 1 second
+   */
+  pub fn retry_delay(mut self, duration: Duration) -> Self {
+    self.retry_delay = duration;
+    self
+  }
+
+  /**
+   * Run whole pipeline: load token, scan, connect and login
+   */
+  pub async fn start(self) -> Result<(Scooter, MiSession), ScooterError> {
+    let token = self.token_source.token_for(&self.addr).await?;
+
+    tracing::info!("Searching scooter with address: {}", self.addr);
+    let mut scanner = ScooterScanner::new().await
+      .map_err(|error| ScooterError::failed(ConnectStage::Scan, error))?;
+
+    let tracked_device = timeout(self.scan_timeout, scanner.wait_for(&self.addr)).await
+      .map_err(|_| ScooterError::Timeout(ConnectStage::Scan))?
+      .map_err(|error| ScooterError::failed(ConnectStage::Scan, error))?;
+
+    let device = scanner.peripheral(&tracked_device).await
+      .map_err(|error| ScooterError::failed(ConnectStage::Scan, error))?;
+
+    let connected = Arc::new(AtomicBool::new(false));
+    let watcher = watch_connection(scanner.central.clone(), device.id(), connected.clone()).await
+      .map_err(|error| ScooterError::failed(ConnectStage::Connect, error))?;
+
+    let mut scooter = Scooter {
+      connection: ConnectionHelper::new(&device),
+      connected,
+      watcher,
+      model: ScooterModel::Unknown,
+      scanner,
+      tracked_device,
+      device,
+      token,
+      connect_timeout: self.connect_timeout,
+      login_timeout: self.login_timeout,
+    };
+
+    let mut attempt = 0;
+    loop {
+      match scooter.reconnect().await {
+        Ok(mut session) => {
+          match session.detect_model().await {
+            Ok(model) => scooter.model = model,
+            Err(error) => tracing::warn!("Could not detect scooter model: {}", error)
+          }
+
+          return Ok((scooter, session))
+        },
+        Err(error) if attempt < self.retries => {
+          attempt += 1;
+          tracing::warn!("Attempt {} failed: {}, retrying...", attempt, error);
+          time::sleep(self.retry_delay).await;
+        },
+        Err(error) => return Err(error)
+      }
+    }
+  }
+}
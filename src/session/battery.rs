@@ -1,12 +1,20 @@
-use super::{MiSession, Payload};
+use super::{MiSession, Payload, FirmwareVersion, ScooterModel};
+use super::registers;
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use crate::transport::ScooterTransport;
 
+use std::fmt;
 use anyhow::Result;
 use serde::Serialize;
 
-pub type BatteryCellsVoltage = [f32; 10];
+pub type BatteryCellsVoltage = Vec<f32>;
 
-#[derive(Debug, Serialize)]
+/**
+ * Most cells that any supported pack has, registers 0x40 - 0x4F
+ */
+const MAX_CELLS : u8 = 16;
+
+#[derive(Clone, Debug, Serialize)]
 pub struct BatteryInfo {
   /**
    * Charge left in scooter, in Milliamps (mA)
@@ -37,7 +45,7 @@ impl TryFrom<Payload> for BatteryInfo {
       BatteryInfo {
         capacity: payload.pop_u16()?,
         percent: payload.pop_u16()?,
-        current: payload.pop_i16()? as f32 / 10.0,
+        current: payload.pop_i16()? as f32 / 100.0,
         voltage: payload.pop_u16()? as f32 / 100.0,
         temperature_1: payload.pad_byte()?,
         temperature_2: payload.pad_byte()?,
@@ -46,96 +54,118 @@ impl TryFrom<Payload> for BatteryInfo {
   }
 }
 
-impl MiSession {
-  /**
-   * Battery voltage in volts
-   */
-  pub async fn battery_voltage(&mut self) -> Result<f32> {
-    tracing::debug!("Reading battery voltage");
+/**
+ * Date packed into u16 by battery management system: 7 bits of year since 2000, 4 bits of month and 5 bits of day
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct ManufactureDate {
+  pub year: u16,
+  pub month: u8,
+  pub day: u8
+}
 
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::BatteryVoltage,
-      payload: vec![0x02]
-    }).await?;
+impl From<u16> for ManufactureDate {
+  fn from(value: u16) -> Self {
+    Self {
+      year: 2000 + (value >> 9),
+      month: ((value >> 5) & 0x0F) as u8,
+      day: (value & 0x1F) as u8
+    }
+  }
+}
 
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
+impl fmt::Display for ManufactureDate {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(fmt, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+  }
+}
 
-    let voltage = payload.pop_u16()? as f32 / 100.0;
+/**
+ * Identity and lifetime statistics of battery pack
+ */
+#[derive(Debug, Serialize)]
+pub struct BatteryPack {
+  pub serial: String,
+  pub firmware_version: FirmwareVersion,
+  /**
+   * Capacity of new battery, in Milliamps (mAh)
+   */
+  pub design_capacity: u16,
+  /**
+   * Capacity of fully charged battery now, in Milliamps (mAh). Compare with design capacity to see how much battery wore out
+   */
+  pub full_charge_capacity: u16,
+  /**
+   * Number of full charge cycles
+   */
+  pub cycles: u16,
+  /**
+   * Number of times battery was plugged in to charger
+   */
+  pub charge_count: u16,
+  pub manufacture_date: ManufactureDate
+}
 
-    Ok(voltage)
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Battery voltage in volts
+   */
+  pub async fn battery_voltage(&mut self) -> Result<f32> {
+    self.read_register(&registers::BATTERY_VOLTAGE).await
   }
 
   /**
    * Return amperage in Ampere
    */
   pub async fn battery_amperage(&mut self) -> Result<f32> {
-    tracing::debug!("Reading battery amperage");
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::BatteryCurrent,
-      payload: vec![0x02]
-    }).await?;
-
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    let amperage = payload.pop_i16()? as f32 / 10.0;
-
-    Ok(amperage)
+    self.read_register(&registers::BATTERY_CURRENT).await
   }
 
   /**
    * Return amperage in Ampere
    */
   pub async fn battery_percentage(&mut self) -> Result<f32> {
-    tracing::debug!("Reading battery amperage");
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::BatteryPercent,
-      payload: vec![0x02]
-    }).await?;
+    self.read_register(&registers::BATTERY_PERCENT).await
+  }
 
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
+  /**
+   * Voltage of every cell in pack, in Volts. For detected model there is one value per cell of its pack and dead cell reads 0V.
+   * Unknown model is read up to 16 cells, and cells that report 0V at the end are taken as not present
+   */
+  pub async fn battery_cell_voltages(&mut self) -> Result<BatteryCellsVoltage> {
+    if self.model() != ScooterModel::Unknown {
+      let cells = self.capabilities().cells;
+      return self.battery_cell_voltages_for(cells).await
+    }
 
-    let percent = payload.pop_u16()? as f32;
+    let mut voltages = self.battery_cell_voltages_for(MAX_CELLS).await?;
+    while voltages.last() == Some(&0.0) {
+      voltages.pop();
+    }
 
-    Ok(percent)
+    Ok(voltages)
   }
 
-  pub async fn battery_cell_voltages(&mut self) -> Result<BatteryCellsVoltage> {
+  /**
+   * Voltage of first cells in pack, in Volts, one value per cell in response. Use it when you know how many cells pack has,
+   * there are no more than 16
+   */
+  pub async fn battery_cell_voltages_for(&mut self, cells: u8) -> Result<BatteryCellsVoltage> {
     tracing::debug!("Reading battery cell voltages");
 
-    self.send(&ScooterCommand {
+    let response = self.request(&ScooterCommand {
       direction: Direction::MasterToBattery,
       read_write: ReadWrite::Read,
       attribute: Attribute::BatteryCellVoltages,
-      payload: vec![0x1B]
+      payload: vec![cells.min(MAX_CELLS) * 2]
     }).await?;
 
-    let mut payload = self.read(3).await?;
-    payload.pop_head()?;
-
-    let voltages : BatteryCellsVoltage = [
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-      payload.pop_u16()? as f32 / 100.0,
-    ];
+    let voltages : BatteryCellsVoltage = response.data
+      .chunks_exact(2)
+      .map(|cell| u16::from_le_bytes([cell[0], cell[1]]) as f32 / 1000.0)
+      .collect();
 
+    tracing::debug!("Cells: {:?}", voltages);
     Ok(voltages)
   }
 
@@ -147,10 +177,48 @@ impl MiSession {
       payload: vec![0x0A]
     }).await?;
 
-    let payload = self.read(2).await?;
+    let payload = self.read().await?;
+
+    BatteryInfo::try_from(payload)
+  }
+
+  /**
+   * Read serial, capacity, cycles and manufacture date of battery pack
+   */
+  pub async fn battery_pack_info(&mut self) -> Result<BatteryPack> {
+    tracing::debug!("Reading battery pack info");
+
+    self.send(&ScooterCommand {
+      direction: Direction::MasterToBattery,
+      read_write: ReadWrite::Read,
+      attribute: Attribute::GeneralInfo,
+      payload: vec![0x1A]
+    }).await?;
+
+    //          [                      SERIAL                          ][ VER  ][DESIGN][ FULL ][  ?   ][CYCLES][CHARGE]
+    let mut payload = self.read().await?;
+    payload.pop_head()?;
+
+    let serial = payload.pop_string_utf8(14)?;
+    let firmware_version = FirmwareVersion::from(payload.pop_u16()?);
+    let design_capacity = payload.pop_u16()?;
+    let full_charge_capacity = payload.pop_u16()?;
+    payload.pad_bytes(2)?;
+    let cycles = payload.pop_u16()?;
+    let charge_count = payload.pop_u16()?;
+
+    let manufacture_date = self.read_register(&registers::BATTERY_MANUFACTURE_DATE).await?;
 
     Ok(
-      BatteryInfo::try_from(payload)?
+      BatteryPack {
+        serial,
+        firmware_version,
+        design_capacity,
+        full_charge_capacity,
+        cycles,
+        charge_count,
+        manufacture_date
+      }
     )
   }
 }
@@ -1,41 +1,86 @@
 use core::fmt::Debug;
 use pretty_hex::*;
+use anyhow::anyhow;
+use super::Payload;
 
-#[derive(Clone)]
+/**
+ * Who sends the command and who receives it
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
   MasterToMotor,
   MasterToBattery,
   MotorToMaster,
   BatteryToMaster,
+  Custom(u8),
 }
 
 impl Direction {
-  fn value(&self) -> u8 {
+  pub fn value(&self) -> u8 {
     match self {
       Direction::MasterToMotor      => 0x20,
       Direction::MasterToBattery    => 0x22,
       Direction::MotorToMaster      => 0x23,
       Direction::BatteryToMaster    => 0x25,
+      Direction::Custom(value)      => *value,
+    }
+  }
+
+  /**
+   * Direction of scooter response for command sent in this direction
+   */
+  pub fn reply(&self) -> Option<Direction> {
+    match Direction::from(self.value()) {
+      Direction::MasterToMotor      => Some(Direction::MotorToMaster),
+      Direction::MasterToBattery    => Some(Direction::BatteryToMaster),
+      _                             => None
     }
   }
 }
 
-#[derive(Clone)]
+impl From<u8> for Direction {
+  fn from(value: u8) -> Self {
+    match value {
+      0x20 => Direction::MasterToMotor,
+      0x22 => Direction::MasterToBattery,
+      0x23 => Direction::MotorToMaster,
+      0x25 => Direction::BatteryToMaster,
+      _    => Direction::Custom(value)
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ReadWrite {
   Read,
-  Write
+  Write,
+  Custom(u8)
 }
 
 impl ReadWrite {
-  fn value(&self) -> u8 {
+  pub fn value(&self) -> u8 {
     match self {
       ReadWrite::Read     => 0x01,
-      ReadWrite::Write    => 0x03
+      ReadWrite::Write    => 0x03,
+      ReadWrite::Custom(value) => *value
     }
   }
 }
 
-#[derive(Clone)]
+impl From<u8> for ReadWrite {
+  fn from(value: u8) -> Self {
+    match value {
+      0x01 => ReadWrite::Read,
+      0x03 => ReadWrite::Write,
+      _    => ReadWrite::Custom(value)
+    }
+  }
+}
+
+/**
+ * Address of register that command reads or writes. Use Custom for registers without own variant
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Attribute {
   GeneralInfo,
   MotorInfo,
@@ -49,11 +94,15 @@ pub enum Attribute {
   Supplementary,
   Cruise,
   TailLight,
-  BatteryInfo
+  BatteryInfo,
+  Lock,
+  Unlock,
+  PowerOff,
+  Custom(u8)
 }
 
 impl Attribute {
-  fn value(&self) -> u8 {
+  pub const fn value(&self) -> u8 {
     match self {
       Attribute::GeneralInfo          => 0x10,
       Attribute::DistanceLeft         => 0x25,
@@ -67,7 +116,11 @@ impl Attribute {
       Attribute::Supplementary        => 0x7B,
       Attribute::Cruise               => 0x7C,
       Attribute::TailLight            => 0x7D,
-      Attribute::BatteryInfo          => 0x31
+      Attribute::BatteryInfo          => 0x31,
+      Attribute::Lock                 => 0x70,
+      Attribute::Unlock               => 0x71,
+      Attribute::PowerOff             => 0x79,
+      Attribute::Custom(address)      => *address
     }
   }
 }
@@ -89,15 +142,70 @@ impl Debug for ScooterCommand {
 }
 
 impl ScooterCommand {
-  pub fn as_bytes(&self) -> Vec<u8> {
-    let mut bytes : Vec<u8> = Vec::new();
-    bytes.push(self.payload.len() as u8 + 2u8);
-    bytes.push(self.direction.value());
-    bytes.push(self.read_write.value());
-    bytes.push(self.attribute.value());
-    for byte in &self.payload {
-      bytes.push(*byte);
+  /**
+   * Command with raw protocol values, for registers and operations this crate does not know about
+   */
+  pub fn raw(direction: u8, read_write: u8, address: u8, payload: Vec<u8>) -> Self {
+    Self {
+      direction: direction.into(),
+      read_write: read_write.into(),
+      attribute: Attribute::Custom(address),
+      payload
     }
+  }
+
+  pub fn as_bytes(&self) -> Vec<u8> {
+    let mut bytes : Vec<u8> = vec![
+      self.payload.len() as u8 + 2u8,
+      self.direction.value(),
+      self.read_write.value(),
+      self.attribute.value()
+    ];
+    bytes.extend_from_slice(&self.payload);
     bytes
   }
 }
+
+/**
+ * Decrypted response from scooter, split into header fields and data
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawResponse {
+  pub direction: Direction,
+  pub read_write: ReadWrite,
+  pub address: u8,
+  pub data: Vec<u8>
+}
+
+/**
+ * Every decrypted response ends with 4 random bytes
+ */
+const RESPONSE_RAND_SIZE : usize = 4;
+
+impl TryFrom<&[u8]> for RawResponse {
+  type Error = anyhow::Error;
+
+  fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+    if bytes.len() < 3 + RESPONSE_RAND_SIZE {
+      return Err(anyhow!("Response is too short: {:?}", bytes.hex_dump()))
+    }
+
+    Ok(
+      RawResponse {
+        direction: bytes[0].into(),
+        read_write: bytes[1].into(),
+        address: bytes[2],
+        data: bytes[3..bytes.len() - RESPONSE_RAND_SIZE].to_vec()
+      }
+    )
+  }
+}
+
+impl RawResponse {
+  /**
+   * Data as payload, ready to pop values. There is no header to pop
+   */
+  pub fn payload(&self) -> Payload {
+    Payload::from(self.data.as_slice())
+  }
+}
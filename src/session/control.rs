@@ -0,0 +1,58 @@
+use super::MiSession;
+use super::registers;
+use super::model::Feature;
+use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use crate::transport::ScooterTransport;
+
+use anyhow::Result;
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Lock scooter, motor is blocked until it is unlocked again. Lock state is read back to make sure scooter applied it
+   */
+  pub async fn lock(&mut self) -> Result<()> {
+    tracing::debug!("Locking scooter");
+    self.require(Feature::Lock)?;
+    self.control_verified(Attribute::Lock, true).await
+  }
+
+  /**
+   * Unlock scooter. Lock state is read back to make sure scooter applied it
+   */
+  pub async fn unlock(&mut self) -> Result<()> {
+    tracing::debug!("Unlocking scooter");
+    self.require(Feature::Lock)?;
+    self.control_verified(Attribute::Unlock, false).await
+  }
+
+  pub async fn is_locked(&mut self) -> Result<bool> {
+    Ok(self.read_register(&registers::STATUS_FLAGS).await?.is_locked())
+  }
+
+  /**
+   * Turn scooter off. It does not answer and connection is usually lost shortly after
+   */
+  pub async fn power_off(&mut self) -> Result<()> {
+    tracing::debug!("Powering off scooter");
+    self.require(Feature::PowerOff)?;
+    self.send(&control_command(Attribute::PowerOff)).await?;
+    Ok(())
+  }
+
+  async fn control_verified(&mut self, attribute: Attribute, locked: bool) -> Result<()> {
+    let expected = if locked { "locked" } else { "unlocked" };
+    self.send_verified(&control_command(attribute), &registers::STATUS_FLAGS, |flags| flags.is_locked() == locked, expected).await
+  }
+}
+
+/**
+ * Lock, unlock and power off are written with 0x0001, see Control commands in doc/protocol.md
+ */
+fn control_command(attribute: Attribute) -> ScooterCommand {
+  ScooterCommand {
+    direction: Direction::MasterToMotor,
+    read_write: ReadWrite::Write,
+    attribute,
+    payload: vec![0x01, 0x00]
+  }
+}
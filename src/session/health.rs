@@ -0,0 +1,237 @@
+use super::{MiSession, BatteryInfo, BatteryPack};
+use crate::transport::ScooterTransport;
+
+use anyhow::Result;
+use serde::Serialize;
+
+/**
+ * Battery management system reports temperatures shifted by 20 degrees, so it does not need negative numbers
+ */
+const TEMPERATURE_OFFSET : i16 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Severity {
+  Ok,
+  Warning,
+  Critical
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum HealthIssue {
+  /**
+   * Difference between highest and lowest cell voltage, in Volts
+   */
+  CellImbalance { delta: f32 },
+  /**
+   * Cell with voltage below the rest of pack, dead cell reports 0V. Index is position in pack, starting from 0
+   */
+  WeakCell { index: usize, voltage: f32 },
+  /**
+   * Full charge capacity compared to design capacity, in percent
+   */
+  CapacityFade { state_of_health: f32 },
+  /**
+   * Temperature in celsius reported by one of sensors. Index starts from 0
+   */
+  Temperature { sensor: usize, celsius: i16 }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HealthFinding {
+  pub severity: Severity,
+  pub issue: HealthIssue
+}
+
+/**
+ * Limits used to decide if something is wrong with battery. Defaults are tuned for 36V li-ion packs
+ */
+#[derive(Clone, Debug)]
+pub struct HealthThresholds {
+  /**
+   * Max minus min cell voltage, in Volts
+   */
+  pub imbalance_warning: f32,
+  pub imbalance_critical: f32,
+  /**
+   * How far below average cell voltage cell is considered weak, in Volts
+   */
+  pub weak_cell_deviation: f32,
+  /**
+   * Cell under this voltage is always critical, in Volts
+   */
+  pub min_cell_voltage: f32,
+  /**
+   * State of health in percent
+   */
+  pub state_of_health_warning: f32,
+  pub state_of_health_critical: f32,
+  /**
+   * Allowed temperature range in celsius, outside of it is warning
+   */
+  pub temperature_min: i16,
+  pub temperature_max: i16,
+  /**
+   * Above this temperature pack is critical
+   */
+  pub temperature_critical: i16
+}
+
+impl Default for HealthThresholds {
+  fn default() -> Self {
+    Self {
+      imbalance_warning: 0.05,
+      imbalance_critical: 0.15,
+      weak_cell_deviation: 0.05,
+      min_cell_voltage: 3.0,
+      state_of_health_warning: 80.0,
+      state_of_health_critical: 60.0,
+      temperature_min: 0,
+      temperature_max: 45,
+      temperature_critical: 60
+    }
+  }
+}
+
+/**
+ * Summary of battery condition with everything that looks wrong
+ */
+#[derive(Clone, Debug, Serialize)]
+pub struct BatteryHealthReport {
+  pub cell_count: usize,
+  /**
+   * Lowest and highest voltage of cells that are not dead, in Volts
+   */
+  pub min_cell_voltage: f32,
+  pub max_cell_voltage: f32,
+  /**
+   * Max minus min cell voltage, in Volts
+   */
+  pub cell_imbalance: f32,
+  /**
+   * Full charge capacity compared to design capacity, in percent
+   */
+  pub state_of_health: Option<f32>,
+  pub temperatures: Vec<i16>,
+  pub findings: Vec<HealthFinding>
+}
+
+impl BatteryHealthReport {
+  /**
+   * Analyze cell voltages with battery info and optional pack info, which is needed for state of health.
+   * Cell with 0V is dead and always critical
+   */
+  pub fn analyze(cells: &[f32], info: &BatteryInfo, pack: Option<&BatteryPack>, thresholds: &HealthThresholds) -> Self {
+    let mut findings = Vec::new();
+
+    // Dead cells are reported as weak cells, they would also drag min and average down and hide weak ones
+    let live_cells : Vec<f32> = cells.iter().copied().filter(|voltage| *voltage > 0.0).collect();
+
+    let min_cell_voltage = live_cells.iter().copied().fold(f32::INFINITY, f32::min);
+    let max_cell_voltage = live_cells.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let (min_cell_voltage, max_cell_voltage) = if live_cells.is_empty() { (0.0, 0.0) } else { (min_cell_voltage, max_cell_voltage) };
+    let cell_imbalance = max_cell_voltage - min_cell_voltage;
+
+    if cell_imbalance >= thresholds.imbalance_critical {
+      findings.push(HealthFinding { severity: Severity::Critical, issue: HealthIssue::CellImbalance { delta: cell_imbalance } });
+    } else if cell_imbalance >= thresholds.imbalance_warning {
+      findings.push(HealthFinding { severity: Severity::Warning, issue: HealthIssue::CellImbalance { delta: cell_imbalance } });
+    }
+
+    if !cells.is_empty() {
+      let average = live_cells.iter().sum::<f32>() / live_cells.len().max(1) as f32;
+
+      for (index, voltage) in cells.iter().copied().enumerate() {
+        let severity = if voltage <= 0.0 || voltage < thresholds.min_cell_voltage {
+          Severity::Critical
+        } else if average - voltage >= thresholds.weak_cell_deviation {
+          Severity::Warning
+        } else {
+          continue
+        };
+
+        findings.push(HealthFinding { severity, issue: HealthIssue::WeakCell { index, voltage } });
+      }
+    }
+
+    let state_of_health = pack
+      .filter(|pack| pack.design_capacity > 0)
+      .map(|pack| pack.full_charge_capacity as f32 / pack.design_capacity as f32 * 100.0);
+
+    if let Some(state_of_health) = state_of_health {
+      let severity = if state_of_health < thresholds.state_of_health_critical {
+        Severity::Critical
+      } else if state_of_health < thresholds.state_of_health_warning {
+        Severity::Warning
+      } else {
+        Severity::Ok
+      };
+
+      if severity != Severity::Ok {
+        findings.push(HealthFinding { severity, issue: HealthIssue::CapacityFade { state_of_health } });
+      }
+    }
+
+    let temperatures : Vec<i16> = [info.temperature_1, info.temperature_2].iter()
+      .map(|raw| *raw as i16 - TEMPERATURE_OFFSET)
+      .collect();
+
+    for (sensor, celsius) in temperatures.iter().copied().enumerate() {
+      let severity = if celsius >= thresholds.temperature_critical {
+        Severity::Critical
+      } else if celsius > thresholds.temperature_max || celsius < thresholds.temperature_min {
+        Severity::Warning
+      } else {
+        continue
+      };
+
+      findings.push(HealthFinding { severity, issue: HealthIssue::Temperature { sensor, celsius } });
+    }
+
+    Self {
+      cell_count: cells.len(),
+      min_cell_voltage,
+      max_cell_voltage,
+      cell_imbalance,
+      state_of_health,
+      temperatures,
+      findings
+    }
+  }
+
+  /**
+   * Worst severity of all findings
+   */
+  pub fn severity(&self) -> Severity {
+    self.findings.iter()
+      .map(|finding| finding.severity)
+      .max()
+      .unwrap_or(Severity::Ok)
+  }
+
+  /**
+   * Indexes of cells reported as weak
+   */
+  pub fn weak_cells(&self) -> Vec<usize> {
+    self.findings.iter()
+      .filter_map(|finding| match finding.issue {
+        HealthIssue::WeakCell { index, .. } => Some(index),
+        _ => None
+      })
+      .collect()
+  }
+}
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Read cell voltages, battery info and pack info and check battery condition with default thresholds
+   */
+  pub async fn battery_health(&mut self) -> Result<BatteryHealthReport> {
+    tracing::debug!("Checking battery health");
+
+    let cells = self.battery_cell_voltages().await?;
+    let info = self.battery_info().await?;
+    let pack = self.battery_pack_info().await?;
+
+    Ok(BatteryHealthReport::analyze(&cells, &info, Some(&pack), &HealthThresholds::default()))
+  }
+}
@@ -1,19 +1,72 @@
 use super::{MiSession, Payload};
+use super::registers;
+use super::status::{ErrorCode, Warnings, StatusFlags, WorkMode};
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use crate::transport::ScooterTransport;
 
+use std::fmt;
 use std::time::Duration;
 use anyhow::Result;
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Serialize)]
 pub struct GeneralInfo {
   serial: String,
   pin: String,
-  version: String
+  version: FirmwareVersion
 }
 
-#[derive(Debug, Serialize)]
+/**
+ * Version packed in nibbles of u16, 0x0134 is 1.3.4
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareVersion {
+  pub major: u8,
+  pub minor: u8,
+  pub patch: u8
+}
+
+impl From<u16> for FirmwareVersion {
+  fn from(value: u16) -> Self {
+    Self {
+      major: ((value >> 8) & 0x0F) as u8,
+      minor: ((value >> 4) & 0x0F) as u8,
+      patch: (value & 0x0F) as u8
+    }
+  }
+}
+
+impl fmt::Display for FirmwareVersion {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FirmwareVersions {
+  /**
+   * Motor controller
+   */
+  pub esc: FirmwareVersion,
+  /**
+   * Bluetooth module
+   */
+  pub ble: FirmwareVersion,
+  /**
+   * Battery management system
+   */
+  pub bms: FirmwareVersion
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct MotorInfo {
+  /**
+   * Error shown on dashboard, ErrorCode::None when everything is fine
+   */
+  pub error: ErrorCode,
+  pub warnings: Warnings,
+  pub flags: StatusFlags,
+  pub work_mode: WorkMode,
   /**
    * Percent value between 0 and 100
    */
@@ -47,7 +100,10 @@ impl TryFrom<Payload> for MotorInfo {
   fn try_from(payload: Payload) -> Result<Self, Self::Error> {
     let mut payload = payload;
     payload.pop_head()?;
-    payload.pad_bytes(8)?; // ---Var179=¿workmode?=0x0000
+    let error = ErrorCode::from(payload.pop_u16()?); // ---Var176=¿error?=0x0000
+    let warnings = Warnings(payload.pop_u16()?); // ---Var177=¿warning?=0x0000
+    let flags = StatusFlags(payload.pop_u16()?); // ---Var178=¿flags?=0x0000=¿?
+    let work_mode = WorkMode::from(payload.pop_u16()?); // ---Var179=¿workmode?=0x0000
 
     let battery_percent = payload.pop_u16()?; // ---Var180=%batt=0x003d=61%
     let speed_kmh = payload.pop_i16()? as f32 / 1000.0; // ---Var181=¿velocidad metros/h?=0x0000=0km/h
@@ -59,6 +115,10 @@ impl TryFrom<Payload> for MotorInfo {
 
     Ok(
       MotorInfo {
+        error,
+        warnings,
+        flags,
+        work_mode,
         battery_percent,
         speed_kmh,
         speed_average_kmh,
@@ -71,7 +131,7 @@ impl TryFrom<Payload> for MotorInfo {
   }
 }
 
-impl MiSession {
+impl<T: ScooterTransport> MiSession<T> {
   pub async fn general_info(&mut self) -> Result<GeneralInfo> {
     tracing::debug!("Reading general information");
 
@@ -84,14 +144,14 @@ impl MiSession {
 
     self.send(&cmd).await?;
     //          [                      SERIAL                          ][          PIN         ][ VER  ]
-    // payload: /x31/x36/x31/x33/x32/x2f/x30/x30/x30/x39/x35/x32/x39/x32/x30/x30/x30/x30/x30/x30/x38/x01
-    let mut payload = self.read(2).await?;
+    // payload: /x31/x32/x33/x34/x35/x2f/x31/x32/x33/x34/x35/x36/x37/x38/x31/x32/x33/x34/x35/x36/x34/x01
+    let mut payload = self.read().await?;
 
     payload.pop_head()?;
 
-    let serial = payload.pop_string_utf8(11)?;
+    let serial = payload.pop_string_utf8(14)?;
     let pin = payload.pop_string_utf8(6)?;
-    let version = payload.pop_string_utf8(2)?;
+    let version = FirmwareVersion::from(payload.pop_u16()?);
 
     Ok(GeneralInfo { serial, pin, version })
   }
@@ -100,21 +160,20 @@ impl MiSession {
    * Read scooter serial number
    */
   pub async fn serial_number(&mut self) -> Result<String> {
-    tracing::debug!("Reading serial number");
-    let cmd = ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::GeneralInfo,
-      payload: vec![0x0e]
-    };
-
-    self.send(&cmd).await?;
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    let serial = payload.pop_string_utf8(14)?;
+    self.read_register(&registers::SERIAL_NUMBER).await
+  }
 
-    Ok(serial)
+  /**
+   * Read firmware versions of motor controller, bluetooth module and battery
+   */
+  pub async fn firmware_versions(&mut self) -> Result<FirmwareVersions> {
+    Ok(
+      FirmwareVersions {
+        esc: self.read_register(&registers::ESC_VERSION).await?,
+        ble: self.read_register(&registers::BLE_VERSION).await?,
+        bms: self.read_register(&registers::BMS_VERSION).await?
+      }
+    )
   }
 
   pub async fn motor_info(&mut self) -> Result<MotorInfo> {
@@ -127,7 +186,7 @@ impl MiSession {
       payload: vec![0x20]
     }).await?;
 
-    let payload = self.read(3).await?;
+    let payload = self.read().await?;
 
     MotorInfo::try_from(payload)
   }
@@ -1,41 +1,267 @@
 pub use super::payload::Payload;
-use super::commands::ScooterCommand;
+use super::commands::{ScooterCommand, ReadWrite, RawResponse};
+use super::model::{ScooterModel, Capabilities, Feature};
+use super::speed::{SpeedPolicy, SpeedViolation};
+use super::telemetry::TelemetryGroup;
 use crate::protocol::MiProtocol;
-use crate::mi_crypto::{encrypt_uart, decrypt_uart, LoginKeychain};
+use crate::mi_crypto::{encrypt_uart, decrypt_uart, uart_counter, LoginKeychain};
 use crate::consts::Registers;
+use crate::transport::{ScooterTransport, TransportError};
 
+use std::time::Duration;
 use anyhow::Result;
+use pretty_hex::*;
 use btleplug::platform::Peripheral;
+use thiserror::Error;
 
-pub struct MiSession {
-  protocol: MiProtocol,
+/**
+ * Only two bytes of message counter are sent in frame
+ */
+const MAX_MESSAGE_COUNTER : u32 = 0xFFFF;
+
+/**
+ * Scooter does not confirm writes, so every setting is read back and written again up to this many times
+ */
+const DEFAULT_WRITE_RETRIES : u8 = 2;
+const DEFAULT_WRITE_RETRY_DELAY : Duration = Duration::from_millis(200);
+
+/**
+ * How many frames not matching last command can be dropped before read gives up
+ */
+const MAX_UNEXPECTED_FRAMES : usize = 8;
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+  #[error("Scooter sent frame with message counter {received}, but counter {last} was already used")]
+  ReplayedFrame { last: u16, received: u16 },
+  #[error("All message counters were used, please login again")]
+  CounterExhausted,
+  #[error("Scooter did not answer to command with direction {direction:#04x} and attribute {attribute:#04x}")]
+  NoMatchingResponse { direction: u8, attribute: u8 },
+  #[error("Register {0:#04x} can't be written")]
+  ReadOnlyRegister(u8),
+  #[error("Scooter did not apply value {expected} to register {register:#04x}, it is still {actual}")]
+  SettingNotApplied { register: u8, expected: String, actual: String },
+  #[error("{model} does not support {feature:?}")]
+  Unsupported { model: ScooterModel, feature: Feature },
+  #[error("{0}")]
+  SpeedLimitExceeded(SpeedViolation),
+  #[error("Speed limit {0} km/h can't be stored by scooter")]
+  InvalidSpeedLimit(f32),
+  #[error("Telemetry interval of {0:?} can't be zero")]
+  ZeroTelemetryInterval(TelemetryGroup)
+}
+
+pub struct MiSession<T: ScooterTransport = Peripheral> {
+  protocol: MiProtocol<T>,
   keys: LoginKeychain,
+  /**
+   * Counter for next message sent to scooter
+   */
+  tx_counter: u32,
+  /**
+   * Counter of last message accepted from scooter
+   */
+  rx_counter: Option<u16>,
+  /**
+   * Direction and attribute of response to last read command
+   */
+  expected: Option<(u8, u8)>,
+  model: ScooterModel,
+  capabilities: Capabilities,
+  /**
+   * How many times setting is written again when read back shows old value
+   */
+  write_retries: u8,
+  write_retry_delay: Duration,
+  speed_policy: SpeedPolicy,
 }
 
-impl MiSession {
-  pub async fn new(device: &Peripheral, keys: &LoginKeychain) -> Result<Self> {
-    let protocol = MiProtocol::new(device).await?;
+impl<T: ScooterTransport> MiSession<T> {
+  pub async fn new(transport: &T, keys: &LoginKeychain) -> Result<Self> {
+    let protocol = MiProtocol::new(transport).await?;
     let keys = keys.clone();
 
-    Ok(Self { protocol, keys })
+    let model = ScooterModel::Unknown;
+    let capabilities = model.capabilities();
+
+    Ok(Self {
+      protocol,
+      keys,
+      tx_counter: 0,
+      rx_counter: None,
+      expected: None,
+      model,
+      capabilities,
+      write_retries: DEFAULT_WRITE_RETRIES,
+      write_retry_delay: DEFAULT_WRITE_RETRY_DELAY,
+      speed_policy: SpeedPolicy::default()
+    })
   }
 
   /**
-   * Serialize, encrypt and send command to scooter
+   * Serialize, encrypt and send command to scooter. Every command is encrypted with next message counter, so nonce is never reused
    */
   pub async fn send(&mut self, cmd: &ScooterCommand) -> Result<bool> {
-    let bytes = encrypt_uart(&self.keys.app, &cmd.as_bytes(), 0, None); // encrypt bytes
+    if self.tx_counter > MAX_MESSAGE_COUNTER {
+      return Err(SessionError::CounterExhausted.into())
+    }
+
+    let bytes = encrypt_uart(&self.keys.app, &cmd.as_bytes(), self.tx_counter, None); // encrypt bytes
+    self.tx_counter += 1;
     self.protocol.write_nb_parcel(&Registers::TX, &bytes).await?;
+
+    if let (ReadWrite::Read, Some(reply)) = (&cmd.read_write, cmd.direction.reply()) {
+      self.expected = Some((reply.value(), cmd.attribute.value()));
+    }
+
     Ok(true)
   }
 
   /**
-   * Wait for response from scooter. You can specify number of frames that you expect to receive
+   * Wait for response from scooter. Responses that do not match direction and attribute of last read command, like late answers
+   * to commands that timed out, are dropped.
+   * Frames with message counter that did not grow since last response are rejected with SessionError::ReplayedFrame
+   */
+  pub async fn read(&mut self) -> Result<Payload> {
+    let response = self.read_response().await?;
+    Ok(Payload::from(response))
+  }
+
+  /**
+   * Wait for response from scooter, same as read, but split into header fields and data
+   */
+  pub async fn read_raw(&mut self) -> Result<RawResponse> {
+    let response = self.read_response().await?;
+    RawResponse::try_from(response.as_slice())
+  }
+
+  /**
+   * Send any command and wait for its response
+   *
+   * ```no_run
+   * # async fn run(mut session: m365::MiSession) -> anyhow::Result<()> {
+   * let response = session.request(&m365::ScooterCommand::raw(0x20, 0x01, 0x1A, vec![0x02])).await?;
+   * println!("{:02x?}", response.data);
+   * # Ok(())
+   * # }
+   * ```
+   */
+  pub async fn request(&mut self, cmd: &ScooterCommand) -> Result<RawResponse> {
+    self.send(cmd).await?;
+    self.read_raw().await
+  }
+
+  /**
+   * Capabilities of detected model, before detection everything is allowed
+   */
+  pub fn capabilities(&self) -> &Capabilities {
+    &self.capabilities
+  }
+
+  /**
+   * Keys derived during login. Anyone who has them can decrypt traffic of this session
+   */
+  pub fn keychain(&self) -> &LoginKeychain {
+    &self.keys
+  }
+
+  pub fn model(&self) -> ScooterModel {
+    self.model
+  }
+
+  /**
+   * Use when you know model better than detection does
+   */
+  pub fn set_model(&mut self, model: ScooterModel) {
+    self.model = model;
+    self.capabilities = model.capabilities();
+  }
+
+  /**
+   * Override capabilities, for example for scooter with aftermarket battery or custom firmware
+   */
+  pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+    self.capabilities = capabilities;
+  }
+
+  pub(crate) fn require(&self, feature: Feature) -> Result<()> {
+    if self.capabilities.supports(feature) {
+      Ok(())
+    } else {
+      Err(SessionError::Unsupported { model: self.model, feature }.into())
+    }
+  }
+
+  /**
+   * How many times setters retry when scooter did not apply value, and how long they wait before reading it back
    */
-  pub async fn read(&mut self, frames: u8) -> Result<Payload> {
-    let data = self.protocol.read_nb_parcel(frames).await?;
+  pub fn set_write_retries(&mut self, retries: u8, delay: Duration) {
+    self.write_retries = retries;
+    self.write_retry_delay = delay;
+  }
+
+  pub fn speed_policy(&self) -> SpeedPolicy {
+    self.speed_policy
+  }
+
+  /**
+   * Regional speed maximum checked by set_speed_profile
+   */
+  pub fn set_speed_policy(&mut self, policy: SpeedPolicy) {
+    self.speed_policy = policy;
+  }
+
+  pub(crate) fn write_retries(&self) -> (u8, Duration) {
+    (self.write_retries, self.write_retry_delay)
+  }
+
+  async fn read_response(&mut self) -> Result<Vec<u8>> {
+    for _ in 0..=MAX_UNEXPECTED_FRAMES {
+      let response = self.read_frame().await?;
+
+      match self.expected {
+        Some((direction, attribute)) if !matches_response(&response, direction, attribute) => {
+          tracing::warn!("Dropping response that does not match last command: {:?}", response.hex_dump());
+        },
+        _ => return Ok(response)
+      }
+    }
+
+    let (direction, attribute) = self.expected.unwrap_or_default();
+    Err(SessionError::NoMatchingResponse { direction, attribute }.into())
+  }
+
+  async fn read_frame(&mut self) -> Result<Vec<u8>> {
+    let data = self.protocol.read_nb_parcel().await?;
+    let counter = uart_counter(&data)?;
+
+    if let Some(last) = self.rx_counter {
+      if counter <= last {
+        tracing::error!("Rejecting replayed frame with counter: {}, last: {}", counter, last);
+        return Err(SessionError::ReplayedFrame { last, received: counter }.into())
+      }
+    }
+
     let response = decrypt_uart(&self.keys.dev, &data)?;
-    let payload = Payload::from(response);
-    Ok(payload)
+    self.rx_counter = Some(counter);
+
+    Ok(response)
   }
 }
+
+fn matches_response(response: &[u8], direction: u8, attribute: u8) -> bool {
+  response.len() >= 3 && response[0] == direction && response[2] == attribute
+}
+
+/**
+ * Session can't be used anymore, either because connection is gone or because all message counters were used.
+ * In both cases the only way forward is connecting and logging in again.
+ */
+pub(crate) fn is_session_lost(error: &anyhow::Error) -> bool {
+  error.chain().any(|cause| {
+    cause.is::<TransportError>()
+      || cause.is::<btleplug::Error>()
+      || matches!(cause.downcast_ref::<SessionError>(), Some(SessionError::CounterExhausted))
+  })
+}
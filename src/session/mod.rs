@@ -1,12 +1,28 @@
 mod mi_session;
-mod commands;
+pub mod commands;
 mod info;
 mod travel;
 mod battery;
 mod payload;
 mod settings;
-pub use mi_session::MiSession;
+mod health;
+mod model;
+mod status;
+mod control;
+mod speed;
+mod telemetry;
+pub mod registers;
+pub use mi_session::{MiSession, SessionError};
+pub(crate) use mi_session::is_session_lost;
 pub use payload::Payload;
-pub use info::{GeneralInfo, MotorInfo};
+pub use info::{GeneralInfo, MotorInfo, FirmwareVersion, FirmwareVersions};
 pub use settings::{TailLight, Kers};
-pub use battery::{BatteryInfo};
+pub use battery::{BatteryInfo, BatteryPack, ManufactureDate};
+pub use travel::TripInfo;
+pub use telemetry::{TelemetryConfig, TelemetrySample, TelemetryFailure, TelemetryGroup};
+pub use health::{BatteryHealthReport, HealthThresholds, HealthFinding, HealthIssue, Severity};
+pub use status::{ErrorCode, Phase, Warnings, StatusFlags, WorkMode};
+pub use model::{ScooterModel, Capabilities, Feature};
+pub use speed::{SpeedProfile, SpeedPolicy, SpeedRule, SpeedViolation};
+pub use registers::Register;
+pub use commands::{ScooterCommand, Direction, ReadWrite, Attribute, RawResponse};
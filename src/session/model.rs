@@ -0,0 +1,172 @@
+use super::{MiSession, FirmwareVersion, registers};
+use crate::transport::ScooterTransport;
+
+use std::fmt;
+use anyhow::Result;
+use serde::{Serialize, Deserialize};
+
+/**
+ * Scooters that speak this protocol
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScooterModel {
+  M365,
+  Essential,
+  OneS,
+  Pro,
+  Pro2,
+  Mi3,
+  Unknown
+}
+
+/**
+ * Settings that are not available on every model
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Feature {
+  Cruise,
+  TailLight,
+  Kers,
+  Lock,
+  PowerOff,
+  SpeedLimit
+}
+
+/**
+ * What scooter model can do
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Capabilities {
+  /**
+   * Top speed in kilometers per hour
+   */
+  pub max_speed_kmh: u8,
+  /**
+   * Number of cells in stock battery pack
+   */
+  pub cells: u8,
+  /**
+   * Capacity of stock battery, in mAh
+   */
+  pub design_capacity: u16,
+  pub features: Vec<Feature>
+}
+
+impl Capabilities {
+  pub fn supports(&self, feature: Feature) -> bool {
+    self.features.contains(&feature)
+  }
+}
+
+/**
+ * Settings with registers documented in doc/protocol.md: cruise 0x7C, tail light 0x7D, kers 0x7B and speed limit 0x73
+ * were captured from M365, lock and power off are in its Control commands section. Every model speaks the same motor
+ * controller protocol and no model is confirmed to miss any of them yet
+ */
+const SHARED_FEATURES : [Feature; 6] = [
+  Feature::Cruise,
+  Feature::TailLight,
+  Feature::Kers,
+  Feature::SpeedLimit,
+  Feature::Lock,
+  Feature::PowerOff
+];
+
+/**
+ * Product code before slash in motor controller serial number, like 16133 in 16133/00123456
+ */
+const SERIAL_PREFIXES : [(&str, ScooterModel); 2] = [
+  ("13678", ScooterModel::M365),
+  ("16133", ScooterModel::M365)
+];
+
+impl ScooterModel {
+  /**
+   * Guess model from serial number, motor controller firmware and capacity of battery, in that order.
+   * Known serial prefix decides right away. Otherwise major firmware version tells generation: 0 is Mi 3 and 3 is 1S,
+   * while 1 is shared by M365 and Pro and 2 by Essential and Pro 2, so capacity of battery picks one of the pair.
+   * Only when firmware is not known model comes from capacity alone, which goes wrong with aftermarket battery.
+   * Use MiSession::set_model when you know better
+   */
+  pub fn detect(serial: &str, esc_version: FirmwareVersion, design_capacity: u16) -> Self {
+    let prefix = serial.split('/').next().unwrap_or_default();
+    if let Some((_, model)) = SERIAL_PREFIXES.iter().find(|(known, _)| *known == prefix) {
+      return *model
+    }
+
+    match (esc_version.major, design_capacity) {
+      (0, _) => ScooterModel::Mi3,
+      (1, 12800) => ScooterModel::Pro,
+      (1, _) => ScooterModel::M365,
+      (2, 5100) => ScooterModel::Essential,
+      (2, 12800) => ScooterModel::Pro2,
+      (3, _) => ScooterModel::OneS,
+      _ => Self::from_capacity(design_capacity)
+    }
+  }
+
+  /**
+   * 1S and Mi 3 ship with the same 7650 mAh pack and Pro and Pro 2 share 12800 mAh pack, capacity alone reports 1S and Pro
+   */
+  fn from_capacity(design_capacity: u16) -> Self {
+    match design_capacity {
+      5100 => ScooterModel::Essential,
+      7650 => ScooterModel::OneS,
+      7800 => ScooterModel::M365,
+      12800 => ScooterModel::Pro,
+      _ => ScooterModel::Unknown
+    }
+  }
+
+  /**
+   * Top speed and battery come from Xiaomi product specifications, every pack is 36V with 10 cells in series.
+   * Features are the ones with documented registers, see SHARED_FEATURES
+   */
+  pub fn capabilities(&self) -> Capabilities {
+    let (max_speed_kmh, cells, design_capacity) = match self {
+      ScooterModel::M365      => (25, 10, 7800),
+      ScooterModel::Essential => (20, 10, 5100),
+      ScooterModel::OneS      => (25, 10, 7650),
+      ScooterModel::Pro       => (25, 10, 12800),
+      ScooterModel::Pro2      => (25, 10, 12800),
+      ScooterModel::Mi3       => (25, 10, 7650),
+      ScooterModel::Unknown   => (25, 16, 0)
+    };
+
+    Capabilities { max_speed_kmh, cells, design_capacity, features: SHARED_FEATURES.to_vec() }
+  }
+}
+
+impl fmt::Display for ScooterModel {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      ScooterModel::M365 => "Mi Electric Scooter M365",
+      ScooterModel::Essential => "Mi Electric Scooter Essential",
+      ScooterModel::OneS => "Mi Electric Scooter 1S",
+      ScooterModel::Pro => "Mi Electric Scooter Pro",
+      ScooterModel::Pro2 => "Mi Electric Scooter Pro 2",
+      ScooterModel::Mi3 => "Mi Electric Scooter 3",
+      ScooterModel::Unknown => "Unknown scooter"
+    };
+
+    fmt.write_str(name)
+  }
+}
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Read serial number, motor controller firmware and battery pack and guess scooter model.
+   * Commands are checked against its capabilities afterwards
+   */
+  pub async fn detect_model(&mut self) -> Result<ScooterModel> {
+    let serial = self.serial_number().await?;
+    let esc_version = self.read_register(&registers::ESC_VERSION).await?;
+    let design_capacity = self.battery_pack_info().await?.design_capacity;
+
+    let model = ScooterModel::detect(&serial, esc_version, design_capacity);
+    tracing::info!("Detected model: {}", model);
+
+    self.set_model(model);
+    Ok(model)
+  }
+}
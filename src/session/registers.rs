@@ -0,0 +1,286 @@
+use super::{MiSession, Payload, SessionError, TailLight, Kers, FirmwareVersion, ManufactureDate, StatusFlags};
+use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use crate::transport::ScooterTransport;
+
+use std::fmt::Debug;
+use std::time::Duration;
+use anyhow::Result;
+use tokio::time;
+
+/**
+ * Decodes value from response payload, header is already removed
+ */
+pub type Decoder<V> = fn(&mut Payload) -> Result<V>;
+
+/**
+ * Encodes value into bytes written to register
+ */
+pub type Encoder<V> = fn(&V) -> Vec<u8>;
+
+/**
+ * Declaration of single register on scooter: where it lives, how many bytes to read and how to turn them into value.
+ * Registers with encoder can be also written:
+ *
+ * ```no_run
+ * # async fn run(mut session: m365::MiSession) -> anyhow::Result<()> {
+ * use m365::{Register, Direction};
+ *
+ * const SPEED_LIMIT : Register<u16> = Register::new(Direction::MasterToMotor, 0x73, 2, |payload| payload.pop_u16())
+ *   .with_encoder(|limit| limit.to_le_bytes().to_vec());
+ *
+ * let limit = session.read_register(&SPEED_LIMIT).await?;
+ * session.write_register(&SPEED_LIMIT, &(limit - 1000)).await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Register<V> {
+  pub direction: Direction,
+  pub address: u8,
+  /**
+   * Number of bytes to read
+   */
+  pub length: u8,
+  decoder: Decoder<V>,
+  encoder: Option<Encoder<V>>
+}
+
+impl<V> Register<V> {
+  pub const fn new(direction: Direction, address: u8, length: u8, decoder: Decoder<V>) -> Self {
+    Self { direction, address, length, decoder, encoder: None }
+  }
+
+  pub const fn with_encoder(mut self, encoder: Encoder<V>) -> Self {
+    self.encoder = Some(encoder);
+    self
+  }
+
+  pub fn is_writable(&self) -> bool {
+    self.encoder.is_some()
+  }
+
+  pub fn read_command(&self) -> ScooterCommand {
+    ScooterCommand {
+      direction: self.direction,
+      read_write: ReadWrite::Read,
+      attribute: Attribute::Custom(self.address),
+      payload: vec![self.length]
+    }
+  }
+
+  pub fn write_command(&self, value: &V) -> Result<ScooterCommand> {
+    let encoder = self.encoder.ok_or(SessionError::ReadOnlyRegister(self.address))?;
+
+    Ok(ScooterCommand {
+      direction: self.direction,
+      read_write: ReadWrite::Write,
+      attribute: Attribute::Custom(self.address),
+      payload: encoder(value)
+    })
+  }
+
+  /**
+   * Decode value from whole response payload
+   */
+  pub fn decode(&self, mut payload: Payload) -> Result<V> {
+    payload.pop_head()?;
+    (self.decoder)(&mut payload)
+  }
+}
+
+pub const SERIAL_NUMBER : Register<String> = Register::new(Direction::MasterToMotor, 0x10, 0x0E, |payload| payload.pop_string_utf8(14));
+
+pub const ESC_VERSION : Register<FirmwareVersion> = Register::new(Direction::MasterToMotor, 0x1A, 0x02, |payload| {
+  Ok(FirmwareVersion::from(payload.pop_u16()?))
+});
+
+/**
+ * Version of battery management system, as reported by motor controller
+ */
+pub const BMS_VERSION : Register<FirmwareVersion> = Register::new(Direction::MasterToMotor, 0x67, 0x02, |payload| {
+  Ok(FirmwareVersion::from(payload.pop_u16()?))
+});
+
+pub const BLE_VERSION : Register<FirmwareVersion> = Register::new(Direction::MasterToMotor, 0x68, 0x02, |payload| {
+  Ok(FirmwareVersion::from(payload.pop_u16()?))
+});
+
+/**
+ * Travel distance left in kilometers
+ */
+pub const DISTANCE_LEFT : Register<f32> = Register::new(Direction::MasterToMotor, 0x25, 0x02, |payload| {
+  Ok(payload.pop_u16()? as f32 / 100.0)
+});
+
+/**
+ * Current speed in kilometers per hour
+ */
+pub const SPEED : Register<f32> = Register::new(Direction::MasterToMotor, 0xB5, 0x02, |payload| {
+  Ok(payload.pop_i16()? as f32 / 1000.0)
+});
+
+/**
+ * Average speed in kilometers per hour, as calculated by motor controller
+ */
+pub const SPEED_AVERAGE : Register<f32> = Register::new(Direction::MasterToMotor, 0xB6, 0x02, |payload| {
+  Ok(payload.pop_u16()? as f32 / 1000.0)
+});
+
+/**
+ * Current trip distance in meters
+ */
+pub const TRIP_DISTANCE : Register<u16> = Register::new(Direction::MasterToMotor, 0xB9, 0x02, |payload| payload.pop_u16());
+
+/**
+ * Time in seconds and distance in meters of current trip
+ */
+pub const TRIP : Register<(Duration, u16)> = Register::new(Direction::MasterToMotor, 0x3A, 0x04, |payload| {
+  let duration = Duration::from_secs(payload.pop_u16()? as u64);
+  let distance_m = payload.pop_u16()?;
+
+  Ok((duration, distance_m))
+});
+
+/**
+ * Frame temperature in celsius, Var62 in doc/protocol.md (0x0118 is 28°C)
+ */
+pub const FRAME_TEMPERATURE : Register<f32> = Register::new(Direction::MasterToMotor, 0x3E, 0x02, |payload| {
+  Ok(payload.pop_i16()? as f32 / 10.0)
+});
+
+pub const STATUS_FLAGS : Register<StatusFlags> = Register::new(Direction::MasterToMotor, 0xB2, 0x02, |payload| {
+  Ok(StatusFlags(payload.pop_u16()?))
+});
+
+pub const CRUISE : Register<bool> = Register::new(Direction::MasterToMotor, 0x7C, 0x02, |payload| payload.pop_bool())
+  .with_encoder(|on| vec![*on as u8, 0x00]);
+
+pub const TAIL_LIGHT : Register<TailLight> = Register::new(Direction::MasterToMotor, 0x7D, 0x02, |payload| {
+  Ok(TailLight::from(payload.pop_u16()?))
+}).with_encoder(|mode| {
+  let mode : u8 = match mode {
+    TailLight::OnBrake => 0x01,
+    TailLight::Always => 0x02,
+    _ => 0x00
+  };
+
+  vec![mode, 0x00]
+});
+
+/**
+ * Strength of regenerative braking
+ */
+pub const KERS : Register<Kers> = Register::new(Direction::MasterToMotor, 0x7B, 0x02, |payload| {
+  Ok(Kers::from(payload.pop_u16()?))
+}).with_encoder(|kers| {
+  let kers : u8 = match kers {
+    Kers::Medium => 0x01,
+    Kers::Strong => 0x02,
+    _ => 0x00
+  };
+
+  vec![kers, 0x00]
+});
+
+/**
+ * Top speed in kilometers per hour, first word of 0x73 in doc/protocol.md (0x4E20 is 20 km/h). Scooter keeps it in meters per hour
+ */
+pub const SPEED_LIMIT : Register<f32> = Register::new(Direction::MasterToMotor, 0x73, 0x02, |payload| {
+  Ok(payload.pop_u16()? as f32 / 1000.0)
+}).with_encoder(|limit| ((limit * 1000.0).round() as u16).to_le_bytes().to_vec());
+
+/**
+ * Battery voltage in volts
+ */
+pub const BATTERY_VOLTAGE : Register<f32> = Register::new(Direction::MasterToBattery, 0x34, 0x02, |payload| {
+  Ok(payload.pop_u16()? as f32 / 100.0)
+});
+
+/**
+ * Battery current in amperes
+ */
+pub const BATTERY_CURRENT : Register<f32> = Register::new(Direction::MasterToBattery, 0x33, 0x02, |payload| {
+  Ok(payload.pop_i16()? as f32 / 100.0)
+});
+
+pub const BATTERY_PERCENT : Register<f32> = Register::new(Direction::MasterToBattery, 0x32, 0x02, |payload| {
+  Ok(payload.pop_u16()? as f32)
+});
+
+/**
+ * Number of full charge cycles and number of charges of battery pack
+ */
+pub const BATTERY_CYCLES : Register<(u16, u16)> = Register::new(Direction::MasterToBattery, 0x1B, 0x04, |payload| {
+  let cycles = payload.pop_u16()?;
+  let charge_count = payload.pop_u16()?;
+
+  Ok((cycles, charge_count))
+});
+
+pub const BATTERY_MANUFACTURE_DATE : Register<ManufactureDate> = Register::new(Direction::MasterToBattery, 0x20, 0x02, |payload| {
+  Ok(ManufactureDate::from(payload.pop_u16()?))
+});
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Read and decode value of register
+   */
+  pub async fn read_register<V>(&mut self, register: &Register<V>) -> Result<V> {
+    tracing::debug!("Reading register {:#04x} from {:?}", register.address, register.direction);
+
+    self.send(&register.read_command()).await?;
+    let payload = self.read().await?;
+
+    register.decode(payload)
+  }
+
+  /**
+   * Encode and write value to register. Fails for registers without encoder
+   */
+  pub async fn write_register<V>(&mut self, register: &Register<V>, value: &V) -> Result<()> {
+    tracing::debug!("Writing register {:#04x} to {:?}", register.address, register.direction);
+
+    self.send(&register.write_command(value)?).await?;
+    Ok(())
+  }
+
+  /**
+   * Write value and read it back, because scooter does not confirm writes. Write is repeated when value did not change,
+   * if it still does not match after all retries SessionError::SettingNotApplied is returned
+   */
+  pub async fn write_register_verified<V: PartialEq + Debug>(&mut self, register: &Register<V>, value: &V) -> Result<()> {
+    let command = register.write_command(value)?;
+    self.send_verified(&command, register, |actual| actual == value, &format!("{:?}", value)).await
+  }
+
+  /**
+   * Send command and read register back until check accepts its value. Command is sent again when check fails,
+   * if it still fails after all retries SessionError::SettingNotApplied is returned with expected description
+   */
+  pub(crate) async fn send_verified<V: Debug>(&mut self, command: &ScooterCommand, register: &Register<V>, check: impl Fn(&V) -> bool, expected: &str) -> Result<()> {
+    let (retries, delay) = self.write_retries();
+    let mut attempt = 0;
+
+    loop {
+      self.send(command).await?;
+      time::sleep(delay).await;
+
+      let actual = self.read_register(register).await?;
+      if check(&actual) {
+        return Ok(())
+      }
+
+      if attempt >= retries {
+        return Err(SessionError::SettingNotApplied {
+          register: register.address,
+          expected: expected.to_owned(),
+          actual: format!("{:?}", actual)
+        }.into())
+      }
+
+      attempt += 1;
+      tracing::warn!("Register {:#04x} is still {:?} instead of {}, sending {:?} again ({})", register.address, actual, expected, command, attempt);
+    }
+  }
+}
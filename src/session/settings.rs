@@ -1,10 +1,13 @@
 use super::{MiSession, Payload};
+use super::registers;
+use super::model::Feature;
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use crate::transport::ScooterTransport;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use serde::Serialize;
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum Kers {
   Weak,
   Medium,
@@ -12,7 +15,7 @@ pub enum Kers {
   Unknown
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum TailLight {
   Off,
   OnBrake,
@@ -66,93 +69,71 @@ impl TryFrom<Payload> for SupplementaryInfo {
   }
 }
 
-impl MiSession {
+impl<T: ScooterTransport> MiSession<T> {
   pub async fn supplementary_info(&mut self) -> Result<SupplementaryInfo> {
     tracing::debug!("Reading supplementary information");
 
     self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
+      direction: Direction::MasterToMotor,
       read_write: ReadWrite::Read,
       attribute: Attribute::Supplementary,
       payload: vec![0x06]
     }).await?;
 
-    let payload = self.read(2).await?;
+    let payload = self.read().await?;
 
-    Ok(SupplementaryInfo::try_from(payload)?)
+    SupplementaryInfo::try_from(payload)
   }
 
   pub async fn is_cruise_on(&mut self) -> Result<bool> {
-    tracing::debug!("Reading cruise state");
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::Cruise,
-      payload: vec![0x02]
-    }).await?;
-
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    Ok(payload.pop_bool()?)
+    self.read_register(&registers::CRUISE).await
   }
 
   pub async fn tail_light(&mut self) -> Result<TailLight> {
-    tracing::debug!("Reading tail light state");
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::TailLight,
-      payload: vec![0x02]
-    }).await?;
-
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    Ok(
-      TailLight::from(payload.pop_u16()?)
-    )
+    self.read_register(&registers::TAIL_LIGHT).await
   }
 
+  /**
+   * Change tail light mode. Value is read back to make sure scooter applied it
+   */
   pub async fn set_tail_light(&mut self, mode : TailLight) -> Result<()> {
     tracing::debug!("Setting tail light: {:?}", mode);
+    self.require(Feature::TailLight)?;
 
-    let mode : u8 = match mode {
-      TailLight::OnBrake => 0x01,
-      TailLight::Always => 0x02,
-      _ => 0x00
-    };
-
-    let payload = vec![mode, 0x00];
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Write,
-      attribute: Attribute::TailLight,
-      payload
-    }).await?;
+    if mode == TailLight::Unknown {
+      return Err(anyhow!("Tail light can be set only to off, on brake or always"))
+    }
 
-    Ok(())
+    self.write_register_verified(&registers::TAIL_LIGHT, &mode).await
   }
 
+  /**
+   * Turn cruise control on or off. Value is read back to make sure scooter applied it
+   */
   pub async fn set_cruise(&mut self, on : bool) -> Result<()> {
     tracing::debug!("Setting cruise enabled: {}", on);
+    self.require(Feature::Cruise)?;
+    self.write_register_verified(&registers::CRUISE, &on).await
+  }
 
-    let payload = if on {
-      vec![0x01, 0x00]
-    } else {
-      vec![0x00, 0x00]
-    };
+  /**
+   * Read strength of regenerative braking
+   */
+  pub async fn kers(&mut self) -> Result<Kers> {
+    self.read_register(&registers::KERS).await
+  }
 
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Write,
-      attribute: Attribute::Cruise,
-      payload
-    }).await?;
+  /**
+   * Change strength of regenerative braking. Value is read back to make sure scooter applied it
+   */
+  pub async fn set_kers(&mut self, kers : Kers) -> Result<()> {
+    tracing::debug!("Setting kers: {:?}", kers);
+    self.require(Feature::Kers)?;
+
+    if kers == Kers::Unknown {
+      return Err(anyhow!("Kers can be set only to weak, medium or strong"))
+    }
 
-    Ok(())
+    self.write_register_verified(&registers::KERS, &kers).await
   }
 }
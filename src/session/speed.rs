@@ -0,0 +1,146 @@
+use super::{MiSession, SessionError, Capabilities, WorkMode};
+use super::registers;
+use super::model::Feature;
+use crate::transport::ScooterTransport;
+
+use std::fmt;
+use anyhow::Result;
+use serde::Serialize;
+
+/**
+ * Speed limit of scooter with mode it rides in. Only limit in 0x73 has documented register, mode is reported in motor
+ * info and can't be written. Walking mode limit has no documented register at all, so it is not part of the profile
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SpeedProfile {
+  /**
+   * Top speed in kilometers per hour, scooter keeps it in whole meters per hour
+   */
+  pub limit_kmh: f32,
+  /**
+   * Mode selected on scooter, read from motor info. Ignored by MiSession::set_speed_profile
+   */
+  pub mode: WorkMode
+}
+
+/**
+ * Highest limit register can keep, scooter stores meters per hour in u16
+ */
+const MAX_STORED_SPEED_KMH : f32 = u16::MAX as f32 / 1000.0;
+
+/**
+ * Regional rules checked before speed profile is written
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct SpeedPolicy {
+  /**
+   * Highest speed allowed by local law, in kilometers per hour
+   */
+  pub regional_max_kmh: Option<f32>,
+  /**
+   * Refuse profiles over regional maximum instead of only warning about them
+   */
+  pub strict: bool
+}
+
+/**
+ * What caps limit that was exceeded
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SpeedRule {
+  /**
+   * Top speed of scooter model
+   */
+  Model,
+  /**
+   * Maximum configured in SpeedPolicy
+   */
+  Regional
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct SpeedViolation {
+  pub rule: SpeedRule,
+  pub limit_kmh: f32,
+  pub max_kmh: f32
+}
+
+impl fmt::Display for SpeedViolation {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let rule = match self.rule {
+      SpeedRule::Model => "model",
+      SpeedRule::Regional => "regional"
+    };
+
+    write!(fmt, "Speed limit {} km/h is over {} maximum {} km/h", self.limit_kmh, rule, self.max_kmh)
+  }
+}
+
+impl SpeedProfile {
+  /**
+   * True when limit is a speed scooter can store: finite, not negative and not over 65.535 km/h
+   */
+  pub fn is_valid(&self) -> bool {
+    self.limit_kmh.is_finite() && (0.0..=MAX_STORED_SPEED_KMH).contains(&self.limit_kmh)
+  }
+
+  /**
+   * Every rule that limit goes over: top speed of model or regional maximum of policy
+   */
+  pub fn violations(&self, capabilities: &Capabilities, policy: &SpeedPolicy) -> Vec<SpeedViolation> {
+    let mut rules = vec![(SpeedRule::Model, capabilities.max_speed_kmh as f32)];
+    if let Some(regional_max_kmh) = policy.regional_max_kmh {
+      rules.push((SpeedRule::Regional, regional_max_kmh));
+    }
+
+    rules.into_iter()
+      .filter(|(_, max_kmh)| self.limit_kmh > *max_kmh)
+      .map(|(rule, max_kmh)| SpeedViolation { rule, limit_kmh: self.limit_kmh, max_kmh })
+      .collect()
+  }
+}
+
+impl<T: ScooterTransport> MiSession<T> {
+  pub async fn speed_profile(&mut self) -> Result<SpeedProfile> {
+    tracing::debug!("Reading speed profile");
+
+    let limit_kmh = self.read_register(&registers::SPEED_LIMIT).await?;
+    let mode = self.motor_info().await?.work_mode;
+
+    Ok(SpeedProfile { limit_kmh, mode })
+  }
+
+  /**
+   * Check speed profile on scooter against model and speed policy of this session
+   */
+  pub async fn audit_speed_profile(&mut self) -> Result<Vec<SpeedViolation>> {
+    let profile = self.speed_profile().await?;
+    Ok(profile.violations(self.capabilities(), &self.speed_policy()))
+  }
+
+  /**
+   * Write speed limit, rounded to whole meters per hour like scooter stores it. Limit over top speed of model is always
+   * refused, limit over regional maximum only in strict mode of speed policy. Value is read back to make sure scooter
+   * applied it. Mode can't be written and is left untouched
+   */
+  pub async fn set_speed_profile(&mut self, profile: &SpeedProfile) -> Result<()> {
+    tracing::debug!("Setting speed profile: {:?}", profile);
+    self.require(Feature::SpeedLimit)?;
+
+    if !profile.is_valid() {
+      return Err(SessionError::InvalidSpeedLimit(profile.limit_kmh).into())
+    }
+
+    let policy = self.speed_policy();
+    for violation in profile.violations(self.capabilities(), &policy) {
+      if violation.rule == SpeedRule::Model || policy.strict {
+        return Err(SessionError::SpeedLimitExceeded(violation).into())
+      }
+
+      tracing::warn!("{}", violation);
+    }
+
+    let limit_kmh = (profile.limit_kmh * 1000.0).round() / 1000.0;
+    self.write_register_verified(&registers::SPEED_LIMIT, &limit_kmh).await
+  }
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+use serde::Serialize;
+
+/**
+ * One of three motor phases
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Phase {
+  A,
+  B,
+  C
+}
+
+impl fmt::Display for Phase {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(self, fmt)
+  }
+}
+
+/**
+ * Error shown on dashboard, numbers are the same as in scooter manual
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ErrorCode {
+  None,
+  /**
+   * 10: Communication between dashboard and motor controller
+   */
+  BleCommunication,
+  /**
+   * 11, 12, 13: Current of motor phase A, B or C
+   */
+  MotorPhaseCurrent(Phase),
+  /**
+   * 14: Throttle
+   */
+  Throttle,
+  /**
+   * 15: Brake
+   */
+  Brake,
+  /**
+   * 18: Motor hall sensor
+   */
+  HallSensor,
+  /**
+   * 21: Communication with battery management system
+   */
+  BmsCommunication,
+  /**
+   * 22: Battery management system password
+   */
+  BmsPassword,
+  /**
+   * 23: Battery has default serial number
+   */
+  BmsSerialNumber,
+  /**
+   * 24: Supply voltage
+   */
+  SupplyVoltage,
+  /**
+   * 28: High side MOSFET of motor controller
+   */
+  HighSideMosfet,
+  /**
+   * 29: Low side MOSFET of motor controller
+   */
+  LowSideMosfet,
+  /**
+   * 39: Battery temperature sensor
+   */
+  BatteryTemperatureSensor,
+  /**
+   * 40: Motor controller is too hot
+   */
+  ControllerOverheat,
+  Other(u16)
+}
+
+impl From<u16> for ErrorCode {
+  fn from(code: u16) -> Self {
+    match code {
+      0  => ErrorCode::None,
+      10 => ErrorCode::BleCommunication,
+      11 => ErrorCode::MotorPhaseCurrent(Phase::A),
+      12 => ErrorCode::MotorPhaseCurrent(Phase::B),
+      13 => ErrorCode::MotorPhaseCurrent(Phase::C),
+      14 => ErrorCode::Throttle,
+      15 => ErrorCode::Brake,
+      18 => ErrorCode::HallSensor,
+      21 => ErrorCode::BmsCommunication,
+      22 => ErrorCode::BmsPassword,
+      23 => ErrorCode::BmsSerialNumber,
+      24 => ErrorCode::SupplyVoltage,
+      28 => ErrorCode::HighSideMosfet,
+      29 => ErrorCode::LowSideMosfet,
+      39 => ErrorCode::BatteryTemperatureSensor,
+      40 => ErrorCode::ControllerOverheat,
+      _  => ErrorCode::Other(code)
+    }
+  }
+}
+
+impl ErrorCode {
+  /**
+   * Number shown on dashboard
+   */
+  pub fn code(&self) -> u16 {
+    match self {
+      ErrorCode::None => 0,
+      ErrorCode::BleCommunication => 10,
+      ErrorCode::MotorPhaseCurrent(Phase::A) => 11,
+      ErrorCode::MotorPhaseCurrent(Phase::B) => 12,
+      ErrorCode::MotorPhaseCurrent(Phase::C) => 13,
+      ErrorCode::Throttle => 14,
+      ErrorCode::Brake => 15,
+      ErrorCode::HallSensor => 18,
+      ErrorCode::BmsCommunication => 21,
+      ErrorCode::BmsPassword => 22,
+      ErrorCode::BmsSerialNumber => 23,
+      ErrorCode::SupplyVoltage => 24,
+      ErrorCode::HighSideMosfet => 28,
+      ErrorCode::LowSideMosfet => 29,
+      ErrorCode::BatteryTemperatureSensor => 39,
+      ErrorCode::ControllerOverheat => 40,
+      ErrorCode::Other(code) => *code
+    }
+  }
+
+  pub fn is_error(&self) -> bool {
+    *self != ErrorCode::None
+  }
+}
+
+impl fmt::Display for ErrorCode {
+  fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ErrorCode::None => write!(fmt, "No error"),
+      ErrorCode::BleCommunication => write!(fmt, "Error 10: dashboard can't communicate with motor controller"),
+      ErrorCode::MotorPhaseCurrent(phase) => write!(fmt, "Error {}: abnormal current in motor phase {}", self.code(), phase),
+      ErrorCode::Throttle => write!(fmt, "Error 14: throttle is abnormal"),
+      ErrorCode::Brake => write!(fmt, "Error 15: brake is abnormal"),
+      ErrorCode::HallSensor => write!(fmt, "Error 18: motor hall sensor is abnormal"),
+      ErrorCode::BmsCommunication => write!(fmt, "Error 21: can't communicate with battery"),
+      ErrorCode::BmsPassword => write!(fmt, "Error 22: battery password is wrong"),
+      ErrorCode::BmsSerialNumber => write!(fmt, "Error 23: battery has default serial number"),
+      ErrorCode::SupplyVoltage => write!(fmt, "Error 24: supply voltage is abnormal"),
+      ErrorCode::HighSideMosfet => write!(fmt, "Error 28: high side MOSFET of motor controller is damaged"),
+      ErrorCode::LowSideMosfet => write!(fmt, "Error 29: low side MOSFET of motor controller is damaged"),
+      ErrorCode::BatteryTemperatureSensor => write!(fmt, "Error 39: battery temperature sensor is abnormal"),
+      ErrorCode::ControllerOverheat => write!(fmt, "Error 40: motor controller is too hot"),
+      ErrorCode::Other(code) => write!(fmt, "Error {}", code)
+    }
+  }
+}
+
+/**
+ * Warning bits reported by motor controller. Meaning of single bits is not documented yet, so they are exposed as is
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Warnings(pub u16);
+
+impl Warnings {
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn contains(&self, bit: u8) -> bool {
+    bit < 16 && self.0 & (1 << bit) != 0
+  }
+
+  /**
+   * Numbers of bits that are set
+   */
+  pub fn active(&self) -> Vec<u8> {
+    (0..16).filter(|bit| self.contains(*bit)).collect()
+  }
+}
+
+/**
+ * Status bits reported by motor controller
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StatusFlags(pub u16);
+
+impl StatusFlags {
+  const LOCKED : u16 = 0x0002;
+
+  /**
+   * Scooter is locked, motor is blocked and it beeps when moved. Bit is described in Control commands of doc/protocol.md
+   */
+  pub fn is_locked(&self) -> bool {
+    self.0 & Self::LOCKED != 0
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum WorkMode {
+  Normal,
+  Eco,
+  Sport,
+  Other(u16)
+}
+
+impl From<u16> for WorkMode {
+  fn from(mode: u16) -> Self {
+    match mode {
+      0 => WorkMode::Normal,
+      1 => WorkMode::Eco,
+      2 => WorkMode::Sport,
+      _ => WorkMode::Other(mode)
+    }
+  }
+}
+
+impl WorkMode {
+  pub fn value(&self) -> u16 {
+    match self {
+      WorkMode::Normal => 0,
+      WorkMode::Eco => 1,
+      WorkMode::Sport => 2,
+      WorkMode::Other(mode) => *mode
+    }
+  }
+}
@@ -0,0 +1,189 @@
+use super::{MiSession, MotorInfo, BatteryInfo, TripInfo, SessionError, is_session_lost};
+use super::battery::BatteryCellsVoltage;
+use crate::transport::ScooterTransport;
+
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use tokio::time::{self, Instant};
+
+/**
+ * How often each group of registers is polled. None means group is not polled at all, zero interval is refused.
+ * Defaults follow Mi Home app, which polls motor info, trip and distance left every second
+ */
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+  pub motor_interval: Option<Duration>,
+  pub trip_interval: Option<Duration>,
+  pub distance_left_interval: Option<Duration>,
+  pub battery_interval: Option<Duration>,
+  pub cell_voltages_interval: Option<Duration>
+}
+
+impl Default for TelemetryConfig {
+  fn default() -> Self {
+    Self {
+      motor_interval: Some(Duration::from_secs(1)),
+      trip_interval: Some(Duration::from_secs(1)),
+      distance_left_interval: Some(Duration::from_secs(1)),
+      battery_interval: Some(Duration::from_secs(5)),
+      cell_voltages_interval: Some(Duration::from_secs(60))
+    }
+  }
+}
+
+/**
+ * Latest known values of every polled group. Groups that were not read yet are None
+ */
+#[derive(Clone, Debug, Serialize)]
+pub struct TelemetrySample {
+  pub timestamp: SystemTime,
+  pub motor: Option<MotorInfo>,
+  pub trip: Option<TripInfo>,
+  /**
+   * Travel distance left in kilometers
+   */
+  pub distance_left_km: Option<f32>,
+  pub battery: Option<BatteryInfo>,
+  pub cell_voltages: Option<BatteryCellsVoltage>,
+  /**
+   * Groups that were due but could not be read this time, their values above are from last successful read
+   */
+  pub failures: Vec<TelemetryFailure>
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TelemetryFailure {
+  pub group: TelemetryGroup,
+  pub error: String
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum TelemetryGroup {
+  Motor,
+  Trip,
+  DistanceLeft,
+  Battery,
+  CellVoltages
+}
+
+struct Schedule {
+  group: TelemetryGroup,
+  interval: Duration,
+  due: Instant
+}
+
+struct Poller<'a, T: ScooterTransport> {
+  session: &'a mut MiSession<T>,
+  schedule: Vec<Schedule>,
+  latest: TelemetrySample,
+  /**
+   * Set when scooter disconnected or message counters ran out, stream ends after returning that error
+   */
+  disconnected: bool
+}
+
+impl<'a, T: ScooterTransport> Poller<'a, T> {
+  fn new(session: &'a mut MiSession<T>, config: TelemetryConfig) -> Result<Self> {
+    let now = Instant::now();
+    let intervals = [
+      (TelemetryGroup::Motor, config.motor_interval),
+      (TelemetryGroup::Trip, config.trip_interval),
+      (TelemetryGroup::DistanceLeft, config.distance_left_interval),
+      (TelemetryGroup::Battery, config.battery_interval),
+      (TelemetryGroup::CellVoltages, config.cell_voltages_interval)
+    ];
+
+    if let Some((group, _)) = intervals.iter().find(|(_, interval)| *interval == Some(Duration::ZERO)) {
+      return Err(SessionError::ZeroTelemetryInterval(*group).into())
+    }
+
+    let schedule = intervals.into_iter()
+      .filter_map(|(group, interval)| interval.map(|interval| Schedule { group, interval, due: now }))
+      .collect();
+
+    let latest = TelemetrySample {
+      timestamp: SystemTime::now(),
+      motor: None,
+      trip: None,
+      distance_left_km: None,
+      battery: None,
+      cell_voltages: None,
+      failures: Vec::new()
+    };
+
+    Ok(Self { session, schedule, latest, disconnected: false })
+  }
+
+  /**
+   * Wait until next group is due and poll every group that is due. When read fails, error is added to failures of sample
+   * and group is polled again after its interval. Only lost session is returned as error
+   */
+  async fn next_sample(&mut self) -> Result<TelemetrySample> {
+    if let Some(due) = self.schedule.iter().map(|schedule| schedule.due).min() {
+      time::sleep_until(due).await;
+    }
+
+    let now = Instant::now();
+    self.latest.failures.clear();
+
+    for index in 0..self.schedule.len() {
+      let schedule = &mut self.schedule[index];
+      if schedule.due > now {
+        continue
+      }
+
+      schedule.due = now + schedule.interval;
+      let group = schedule.group;
+
+      if let Err(error) = self.poll(group).await {
+        if is_session_lost(&error) {
+          self.disconnected = true;
+          return Err(error)
+        }
+
+        tracing::warn!("Could not read telemetry {:?}: {}", group, error);
+        self.latest.failures.push(TelemetryFailure { group, error: error.to_string() });
+      }
+    }
+
+    self.latest.timestamp = SystemTime::now();
+    Ok(self.latest.clone())
+  }
+
+  async fn poll(&mut self, group: TelemetryGroup) -> Result<()> {
+    tracing::debug!("Polling telemetry: {:?}", group);
+
+    match group {
+      TelemetryGroup::Motor => self.latest.motor = Some(self.session.motor_info().await?),
+      TelemetryGroup::Trip => self.latest.trip = Some(self.session.trip_info().await?),
+      TelemetryGroup::DistanceLeft => self.latest.distance_left_km = Some(self.session.distance_left().await?),
+      TelemetryGroup::Battery => self.latest.battery = Some(self.session.battery_info().await?),
+      TelemetryGroup::CellVoltages => self.latest.cell_voltages = Some(self.session.battery_cell_voltages().await?)
+    }
+
+    Ok(())
+  }
+}
+
+impl<T: ScooterTransport> MiSession<T> {
+  /**
+   * Poll motor, trip and battery registers in a loop like Mi Home app does. Every item is merged with latest values
+   * of groups that were not due yet or could not be read, failed groups are listed in its failures.
+   * When scooter disconnects or message counters run out the error is returned as last item and stream ends, it also ends when no group has interval.
+   * Zero interval is refused with SessionError::ZeroTelemetryInterval
+   */
+  pub fn telemetry_stream(&mut self, config: TelemetryConfig) -> Result<impl Stream<Item = Result<TelemetrySample>> + '_> {
+    let poller = Poller::new(self, config)?;
+
+    Ok(stream::unfold(poller, |mut poller| async move {
+      if poller.schedule.is_empty() || poller.disconnected {
+        return None
+      }
+
+      let sample = poller.next_sample().await;
+      Some((sample, poller))
+    }))
+  }
+}
@@ -1,29 +1,33 @@
 use super::MiSession;
-use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
+use super::registers;
+use crate::transport::ScooterTransport;
 
+use std::time::Duration;
 use anyhow::Result;
+use serde::Serialize;
+
+/**
+ * Statistics of current trip
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TripInfo {
+  pub duration: Duration,
+  /**
+   * Distance is in meters
+   */
+  pub distance_m: u16,
+  /**
+   * Average speed in kilometers per hour, as reported by motor controller
+   */
+  pub average_speed_kmh: f32
+}
 
-impl MiSession {
+impl<T: ScooterTransport> MiSession<T> {
   /**
    * Get travel distance left in kilometers
    */
   pub async fn distance_left(&mut self) -> Result<f32> {
-    tracing::debug!("Reading distance left");
-
-    let cmd = ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::DistanceLeft,
-      payload: vec![0x02]
-    };
-
-    self.send(&cmd).await?;
-
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    let distance_left = payload.pop_u16()?;
-    let distance_left = distance_left as f32 / 100.0;
+    let distance_left = self.read_register(&registers::DISTANCE_LEFT).await?;
     tracing::debug!("Distance left: {}km", distance_left);
 
     Ok(distance_left)
@@ -33,22 +37,7 @@ impl MiSession {
    * Get current speed in kilometers per hour
    */
   pub async fn speed(&mut self) -> Result<f32> {
-    tracing::debug!("Reading speed");
-
-    let cmd = ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::Speed,
-      payload: vec![0x02]
-    };
-
-    self.send(&cmd).await?;
-
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
-
-    let speed = payload.pop_i16()?;
-    let speed = speed as f32 / 1000.0;
+    let speed = self.read_register(&registers::SPEED).await?;
     tracing::debug!("speed: {}km/h", speed);
 
     Ok(speed)
@@ -58,22 +47,19 @@ impl MiSession {
    * Read current travel distance in meters
    */
   pub async fn trip_distance(&mut self) -> Result<u16> {
-    tracing::debug!("Reading distance");
-
-    let cmd = ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::TripDistance,
-      payload: vec![0x02]
-    };
-
-    self.send(&cmd).await?;
+    self.read_register(&registers::TRIP_DISTANCE).await
+  }
 
-    let mut payload = self.read(3).await?;
-    payload.pop_head()?;
+  /**
+   * Read duration, distance and average speed of current trip
+   */
+  pub async fn trip_info(&mut self) -> Result<TripInfo> {
+    let (duration, distance_m) = self.read_register(&registers::TRIP).await?;
+    let average_speed_kmh = self.read_register(&registers::SPEED_AVERAGE).await?;
 
-    let trip_distance = payload.pop_u16()?;
+    let trip_info = TripInfo { duration, distance_m, average_speed_kmh };
+    tracing::debug!("Trip: {:?}", trip_info);
 
-    Ok(trip_distance)
+    Ok(trip_info)
   }
 }
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+/**
+ * Scooter has two boards that answer uart commands, each with own register table
+ */
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Bank {
+  Motor,
+  Battery
+}
+
+impl Bank {
+  pub fn from_direction(direction: u8) -> Option<Self> {
+    match direction {
+      0x20 => Some(Bank::Motor),
+      0x22 => Some(Bank::Battery),
+      _ => None
+    }
+  }
+
+  /**
+   * Direction byte used by this board when it answers master
+   */
+  pub fn reply_direction(&self) -> u8 {
+    match self {
+      Bank::Motor => 0x23,
+      Bank::Battery => 0x25
+    }
+  }
+}
+
+/**
+ * Register table of simulated scooter. Registers are 16 bit words addressed the same way as in uart commands,
+ * so reading 0x20 bytes from 0xB0 returns sixteen words from 0xB0 to 0xBF. Registers that were never written read as zero.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+  words: HashMap<(Bank, u8), u16>
+}
+
+impl Memory {
+  pub fn read_u16(&self, bank: Bank, address: u8) -> u16 {
+    *self.words.get(&(bank, address)).unwrap_or(&0)
+  }
+
+  pub fn write_u16(&mut self, bank: Bank, address: u8, value: u16) {
+    self.words.insert((bank, address), value);
+  }
+
+  /**
+   * Write u32 as two words, least significant word first
+   */
+  pub fn write_u32(&mut self, bank: Bank, address: u8, value: u32) {
+    self.write_bytes(bank, address, &value.to_le_bytes());
+  }
+
+  /**
+   * Write ascii string, two characters per register
+   */
+  pub fn write_string(&mut self, bank: Bank, address: u8, value: &str) {
+    self.write_bytes(bank, address, value.as_bytes());
+  }
+
+  /**
+   * Write raw little endian bytes starting from address. Odd length is padded with zero
+   */
+  pub fn write_bytes(&mut self, bank: Bank, address: u8, bytes: &[u8]) {
+    for (index, chunk) in bytes.chunks(2).enumerate() {
+      let low = chunk[0];
+      let high = chunk.get(1).copied().unwrap_or(0);
+      self.write_u16(bank, address.wrapping_add(index as u8), u16::from_le_bytes([low, high]));
+    }
+  }
+
+  /**
+   * Read length bytes starting from address
+   */
+  pub fn read_bytes(&self, bank: Bank, address: u8, length: usize) -> Vec<u8> {
+    let mut bytes : Vec<u8> = Vec::new();
+    let mut offset = 0;
+
+    while bytes.len() < length {
+      let word = self.read_u16(bank, address.wrapping_add(offset));
+      bytes.extend_from_slice(&word.to_le_bytes());
+      offset = offset.wrapping_add(1);
+    }
+
+    bytes.truncate(length);
+    bytes
+  }
+}
@@ -0,0 +1,6 @@
+mod memory;
+mod scooter;
+mod transport;
+pub use memory::{Bank, Memory};
+pub use scooter::SimulatedScooter;
+pub use transport::SimulatorTransport;
@@ -0,0 +1,479 @@
+use super::memory::{Bank, Memory};
+use crate::consts::{MiCommands, Registers};
+use crate::protocol::{NB_CHUNK_SIZE, MI_CHUNK_SIZE, NB_FRAME_OVERHEAD};
+use crate::mi_crypto::{
+  AuthToken, Hash, LoginKeychain, RandKey,
+  calc_login_did, calc_remote_did, gen_rand_key, encrypt_uart, decrypt_uart, uart_counter
+};
+
+use std::collections::VecDeque;
+use btleplug::api::ValueNotification;
+use p256::{EncodedPoint, ecdh::EphemeralSecret};
+use rand_core::OsRng;
+use pretty_hex::*;
+
+/**
+ * Bit of motor status flags (0xB2) set while scooter is locked
+ */
+const LOCKED_FLAG : u16 = 0x0002;
+
+/**
+ * Device info sent during registration, did starts at 4th byte
+ */
+const REMOTE_INFO : [u8; 24] = [
+  0x01, 0x00, 0x00, 0x00, 0x00, 0x62, 0x6c, 0x74, 0x2e, 0x33, 0x2e, 0x31,
+  0x36, 0x33, 0x39, 0x34, 0x74, 0x33, 0x67, 0x34, 0x6c, 0x63, 0x30, 0x30
+];
+
+/**
+ * What app is uploading in mi parcel
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Upload {
+  PublicKey,
+  Did,
+  RandKey,
+  Info
+}
+
+struct IncomingParcel {
+  kind: Upload,
+  frames: u8,
+  data: Vec<u8>
+}
+
+/**
+ * Plays scooter side of registration, login and uart exchanges. Feed it with everything app writes using `handle`
+ * and it returns notifications that real scooter would send back.
+ */
+pub struct SimulatedScooter {
+  pub memory: Memory,
+  token: Option<AuthToken>,
+  secret: Option<EphemeralSecret>,
+  app_public_key: Option<Vec<u8>>,
+  expected_info: Option<Hash>,
+  pending_keys: Option<LoginKeychain>,
+  keys: Option<LoginKeychain>,
+  incoming: Option<IncomingParcel>,
+  outgoing: VecDeque<Vec<u8>>,
+  uart_buffer: Vec<u8>,
+  tx_counter: u32,
+  rx_counter: Option<u16>,
+  outbox: Vec<ValueNotification>,
+  ignored_writes: usize,
+}
+
+impl Default for SimulatedScooter {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SimulatedScooter {
+  /**
+   * New scooter that was never paired, with register table filled with values of a parked scooter
+   */
+  pub fn new() -> Self {
+    let mut memory = Memory::default();
+    fill_registers(&mut memory);
+
+    Self {
+      memory,
+      token: None,
+      secret: None,
+      app_public_key: None,
+      expected_info: None,
+      pending_keys: None,
+      keys: None,
+      incoming: None,
+      outgoing: VecDeque::new(),
+      uart_buffer: Vec::new(),
+      tx_counter: 0,
+      rx_counter: None,
+      outbox: Vec::new(),
+      ignored_writes: 0,
+    }
+  }
+
+  /**
+   * Scooter that already accepted registration for this token
+   */
+  pub fn with_token(token: &AuthToken) -> Self {
+    let mut scooter = Self::new();
+    scooter.token = Some(*token);
+    scooter
+  }
+
+  /**
+   * Token negotiated during last successful registration
+   */
+  pub fn token(&self) -> Option<AuthToken> {
+    self.token
+  }
+
+  /**
+   * Returns true after app logged in and can send uart commands
+   */
+  pub fn is_logged_in(&self) -> bool {
+    self.keys.is_some()
+  }
+
+  /**
+   * Start numbering responses from zero again, like a misbehaving scooter that replays old frames
+   */
+  pub fn reset_message_counter(&mut self) {
+    self.tx_counter = 0;
+  }
+
+  /**
+   * Silently drop next `count` register writes, like scooter that did not apply setting
+   */
+  pub fn ignore_writes(&mut self, count: usize) {
+    self.ignored_writes = count;
+  }
+
+  /**
+   * Queue raw notification, it is delivered before response to next write
+   */
+  pub fn queue_notification(&mut self, reg: Registers, value: Vec<u8>) {
+    self.notify(reg, value);
+  }
+
+  /**
+   * Queue reply for register that app did not ask for, like late answer to command that already timed out.
+   * It is delivered before response to next write
+   */
+  pub fn queue_register_reply(&mut self, bank: Bank, attribute: u8, length: usize) {
+    match self.keys.clone() {
+      Some(keys) => self.reply_register(&keys, bank, attribute, length),
+      None => tracing::error!("Simulator can't send uart reply before login")
+    }
+  }
+
+  /**
+   * Handle bytes written by app to register, and return notifications scooter sends back
+   */
+  pub fn handle(&mut self, reg: &Registers, data: &[u8]) -> Vec<ValueNotification> {
+    tracing::debug!("Simulator <- {:?}: {:?}", reg, data.hex_dump());
+
+    match reg {
+      Registers::UPNP => self.handle_upnp(data),
+      Registers::AVDTP => self.handle_avdtp(data),
+      Registers::TX => self.handle_uart(data),
+      _ => tracing::error!("Simulator can't handle writes to {:?}", reg)
+    }
+
+    self.outbox.drain(..).collect()
+  }
+
+  fn notify(&mut self, reg: Registers, value: Vec<u8>) {
+    tracing::debug!("Simulator -> {:?}: {:?}", reg, value.hex_dump());
+    self.outbox.push(ValueNotification { uuid: reg.to_uuid(), value });
+  }
+
+  fn notify_command(&mut self, reg: Registers, command: MiCommands) {
+    self.notify(reg, command.to_bytes());
+  }
+
+  fn handle_upnp(&mut self, data: &[u8]) {
+    if data == MiCommands::CMD_GET_INFO.to_bytes() {
+      self.send_parcel(REMOTE_INFO.to_vec());
+    } else if data == MiCommands::CMD_SET_KEY.to_bytes() {
+      self.secret = None;
+    } else if data == MiCommands::CMD_AUTH.to_bytes() {
+      if self.token.is_some() && self.secret.is_none() {
+        self.notify_command(Registers::UPNP, MiCommands::RCV_AUTH_OK);
+      } else {
+        self.notify_command(Registers::UPNP, MiCommands::RCV_AUTH_ERR);
+      }
+    } else if data == MiCommands::CMD_LOGIN.to_bytes() {
+      self.keys = None;
+      self.pending_keys = None;
+    } else {
+      tracing::error!("Simulator received unknown UPNP command: {:?}", data.hex_dump());
+    }
+  }
+
+  fn handle_avdtp(&mut self, data: &[u8]) {
+    if data == MiCommands::RCV_RDY.to_bytes() {
+      self.send_parcel_frames();
+    } else if data == MiCommands::RCV_OK.to_bytes() {
+      self.outgoing.pop_front();
+      if let Some(parcel) = self.outgoing.front() {
+        let header = parcel_header(parcel);
+        self.notify(Registers::AVDTP, header);
+      }
+    } else if let Some(kind) = upload_kind(data) {
+      self.incoming = Some(IncomingParcel { kind, frames: data[4], data: Vec::new() });
+      self.notify_command(Registers::AVDTP, MiCommands::RCV_RDY);
+    } else if let Some(incoming) = self.incoming.as_mut() {
+      incoming.data.extend_from_slice(&data[2..]);
+
+      if data[0] == incoming.frames {
+        let incoming = self.incoming.take().unwrap();
+        self.notify_command(Registers::AVDTP, MiCommands::RCV_OK);
+        self.receive_parcel(incoming.kind, &incoming.data);
+      }
+    } else {
+      tracing::error!("Simulator received unexpected AVDTP data: {:?}", data.hex_dump());
+    }
+  }
+
+  fn receive_parcel(&mut self, kind: Upload, data: &[u8]) {
+    match kind {
+      Upload::PublicKey => {
+        let secret = EphemeralSecret::random(&mut OsRng);
+        let public_key = EncodedPoint::from(secret.public_key());
+
+        self.secret = Some(secret);
+        self.app_public_key = Some([&[0x04], data].concat());
+        self.token = None;
+
+        self.send_parcel(public_key.as_bytes()[1..].to_vec());
+      },
+
+      Upload::Did => {
+        let secret = self.secret.take();
+        let app_key = self.app_public_key.take();
+
+        if let (Some(secret), Some(app_key)) = (secret, app_key) {
+          match calc_remote_did(&secret, &app_key, data) {
+            Ok((did, token)) if did == REMOTE_INFO[4..] => {
+              tracing::debug!("Simulator accepted did, token: {:?}", token.hex_dump());
+              self.token = Some(token);
+            },
+            _ => {
+              tracing::error!("Simulator could not verify did");
+              self.secret = Some(secret);
+            }
+          }
+        }
+      },
+
+      Upload::RandKey => {
+        let app_rand_key : RandKey = match data.get(0..16).and_then(|key| key.try_into().ok()) {
+          Some(key) => key,
+          None => return
+        };
+
+        if let Some(token) = self.token {
+          let mut app_rand = app_rand_key;
+          let mut scooter_rand = gen_rand_key();
+          let (info, remote_info, keys) = calc_login_did(&mut app_rand, &mut scooter_rand, &token);
+
+          self.expected_info = Some(info);
+          self.pending_keys = Some(keys);
+          self.send_parcel(scooter_rand.to_vec());
+          self.outgoing.push_back(remote_info.to_vec());
+        } else {
+          tracing::error!("Simulator is not registered, login is not possible");
+        }
+      },
+
+      Upload::Info => {
+        if self.expected_info.take().is_some_and(|info| data.get(0..32) == Some(&info[..])) {
+          self.keys = self.pending_keys.take();
+          self.tx_counter = 0;
+          self.rx_counter = None;
+          self.notify_command(Registers::UPNP, MiCommands::RCV_LOGIN_OK);
+        } else {
+          self.notify_command(Registers::UPNP, MiCommands::RCV_LOGIN_ERR);
+        }
+      }
+    }
+  }
+
+  /**
+   * Queue parcel and announce it, if nothing else is being sent right now
+   */
+  fn send_parcel(&mut self, parcel: Vec<u8>) {
+    self.outgoing.push_back(parcel);
+
+    if self.outgoing.len() == 1 {
+      let header = parcel_header(&self.outgoing[0]);
+      self.notify(Registers::AVDTP, header);
+    }
+  }
+
+  fn send_parcel_frames(&mut self) {
+    let parcel = match self.outgoing.front() {
+      Some(parcel) => parcel.clone(),
+      None => return
+    };
+
+    for (index, chunk) in parcel.chunks(MI_CHUNK_SIZE).enumerate() {
+      let mut frame = vec![index as u8 + 1, 0];
+      frame.extend_from_slice(chunk);
+      self.notify(Registers::AVDTP, frame);
+    }
+  }
+
+  fn handle_uart(&mut self, data: &[u8]) {
+    self.uart_buffer.extend_from_slice(data);
+
+    while self.uart_buffer.len() >= 3 {
+      let frame_size = self.uart_buffer[2] as usize + NB_FRAME_OVERHEAD;
+      if self.uart_buffer.len() < frame_size {
+        break;
+      }
+
+      let frame : Vec<u8> = self.uart_buffer.drain(0..frame_size).collect();
+      self.handle_uart_frame(&frame);
+    }
+  }
+
+  fn handle_uart_frame(&mut self, frame: &[u8]) {
+    let keys = match &self.keys {
+      Some(keys) => keys.clone(),
+      None => {
+        tracing::error!("Simulator received uart frame before login");
+        return
+      }
+    };
+
+    let counter = uart_counter(frame).unwrap_or(0);
+    if self.rx_counter.is_some_and(|last| counter <= last) {
+      tracing::error!("Simulator dropped uart frame with reused message counter: {}", counter);
+      return
+    }
+
+    let msg = match decrypt_uart(&keys.app, frame) {
+      Ok(msg) => msg,
+      Err(err) => {
+        tracing::error!("Simulator could not decrypt uart frame: {}", err);
+        return
+      }
+    };
+
+    let payload_size = match (frame[2] as usize).checked_sub(2) {
+      Some(size) if msg.len() >= 3 + size => size,
+      _ => {
+        tracing::error!("Simulator dropped malformed uart frame with length: {}", frame[2]);
+        return
+      }
+    };
+    self.rx_counter = Some(counter);
+
+    let (direction, read_write, attribute) = (msg[0], msg[1], msg[2]);
+    let payload = &msg[3..3 + payload_size];
+
+    let bank = match Bank::from_direction(direction) {
+      Some(bank) => bank,
+      None => {
+        tracing::error!("Simulator received uart frame for unknown direction: {:x}", direction);
+        return
+      }
+    };
+
+    match read_write {
+      0x01 => {
+        let length = payload.first().copied().unwrap_or(2) as usize;
+        self.reply_register(&keys, bank, attribute, length);
+      },
+
+      0x03 if self.ignored_writes > 0 => {
+        tracing::warn!("Simulator ignored write to register {:x}", attribute);
+        self.ignored_writes -= 1;
+      },
+
+      0x03 => {
+        self.memory.write_bytes(bank, attribute, payload);
+        self.apply_control(bank, attribute);
+      },
+
+      _ => tracing::error!("Simulator received unknown uart operation: {:x}", read_write)
+    }
+  }
+
+  /**
+   * Lock and unlock commands change status flags of motor controller
+   */
+  fn apply_control(&mut self, bank: Bank, attribute: u8) {
+    let flags = self.memory.read_u16(Bank::Motor, 0xB2);
+
+    match (bank, attribute) {
+      (Bank::Motor, 0x70) => self.memory.write_u16(Bank::Motor, 0xB2, flags | LOCKED_FLAG),
+      (Bank::Motor, 0x71) => self.memory.write_u16(Bank::Motor, 0xB2, flags & !LOCKED_FLAG),
+      _ => {}
+    }
+  }
+
+  fn reply_register(&mut self, keys: &LoginKeychain, bank: Bank, attribute: u8, length: usize) {
+    let data = self.memory.read_bytes(bank, attribute, length);
+
+    let mut response = vec![data.len() as u8 + 2, bank.reply_direction(), 0x01, attribute];
+    response.extend_from_slice(&data);
+
+    let response = encrypt_uart(&keys.dev, &response, self.tx_counter, None);
+    self.tx_counter += 1;
+    for chunk in response.chunks(NB_CHUNK_SIZE) {
+      self.notify(Registers::RX, chunk.to_vec());
+    }
+  }
+}
+
+fn upload_kind(data: &[u8]) -> Option<Upload> {
+  let data = data.to_vec();
+
+  if data == MiCommands::CMD_SEND_DATA.to_bytes() {
+    Some(Upload::PublicKey)
+  } else if data == MiCommands::CMD_SEND_DID.to_bytes() {
+    Some(Upload::Did)
+  } else if data == MiCommands::CMD_SEND_KEY.to_bytes() {
+    Some(Upload::RandKey)
+  } else if data == MiCommands::CMD_SEND_INFO.to_bytes() {
+    Some(Upload::Info)
+  } else {
+    None
+  }
+}
+
+fn parcel_header(parcel: &[u8]) -> Vec<u8> {
+  let frames = parcel.len().div_ceil(MI_CHUNK_SIZE) as u16;
+  let frames = frames.to_le_bytes();
+
+  vec![0x00, 0x00, 0x00, 0x0d, frames[0], frames[1]]
+}
+
+/**
+ * Values of a parked M365 with 63% battery
+ */
+fn fill_registers(memory: &mut Memory) {
+  // Motor controller
+  memory.write_string(Bank::Motor, 0x10, "26354/00467353");
+  memory.write_string(Bank::Motor, 0x17, "000000");
+  memory.write_u16(Bank::Motor, 0x1A, 0x0134);
+  memory.write_u16(Bank::Motor, 0x25, 2835);
+  memory.write_u16(Bank::Motor, 0x3A, 635);
+  memory.write_u16(Bank::Motor, 0x3B, 2540);
+  memory.write_u16(Bank::Motor, 0x3E, 250);
+  memory.write_u16(Bank::Motor, 0x67, 0x0115);
+  memory.write_u16(Bank::Motor, 0x68, 0x0090);
+  memory.write_bytes(Bank::Motor, 0x73, &[0x20, 0x4E, 0x10, 0x27]);
+  memory.write_u16(Bank::Motor, 0x7B, 0);
+  memory.write_u16(Bank::Motor, 0x7C, 0);
+  memory.write_u16(Bank::Motor, 0x7D, 0);
+  memory.write_u16(Bank::Motor, 0xB4, 63);
+  memory.write_u16(Bank::Motor, 0xB6, 18000);
+  memory.write_u32(Bank::Motor, 0xB7, 1306083);
+  memory.write_u16(Bank::Motor, 0xBA, 88);
+  memory.write_u16(Bank::Motor, 0xBB, 250);
+
+  // Battery management system
+  memory.write_string(Bank::Battery, 0x10, "3LABATTDECAMIL");
+  memory.write_u16(Bank::Battery, 0x17, 0x0115);
+  memory.write_u16(Bank::Battery, 0x18, 7800);
+  memory.write_u16(Bank::Battery, 0x19, 7600);
+  memory.write_u16(Bank::Battery, 0x1B, 12);
+  memory.write_u16(Bank::Battery, 0x1C, 45);
+  memory.write_u16(Bank::Battery, 0x20, 0x22A2);
+  memory.write_u16(Bank::Battery, 0x31, 7417);
+  memory.write_u16(Bank::Battery, 0x32, 63);
+  memory.write_u16(Bank::Battery, 0x33, 1);
+  memory.write_u16(Bank::Battery, 0x34, 3676);
+  memory.write_bytes(Bank::Battery, 0x35, &[45, 45]);
+
+  for cell in 0..10 {
+    memory.write_u16(Bank::Battery, 0x40 + cell, 3676);
+  }
+}
@@ -0,0 +1,94 @@
+use super::SimulatedScooter;
+use crate::consts::Registers;
+use crate::transport::{ScooterTransport, NotificationStream, TransportError};
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use async_trait::async_trait;
+use btleplug::api::ValueNotification;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use anyhow::{Result, anyhow};
+
+type Subscriber = Arc<Mutex<Option<mpsc::UnboundedSender<ValueNotification>>>>;
+
+/**
+ * Transport connected directly to SimulatedScooter, no bluetooth adapter needed.
+ * Clones share the same scooter, so you can inspect its state while session is running.
+ */
+#[derive(Clone)]
+pub struct SimulatorTransport {
+  scooter: Arc<Mutex<SimulatedScooter>>,
+  subscriber: Subscriber,
+  connected: Arc<AtomicBool>
+}
+
+impl SimulatorTransport {
+  pub fn new(scooter: SimulatedScooter) -> Self {
+    Self {
+      scooter: Arc::new(Mutex::new(scooter)),
+      subscriber: Arc::new(Mutex::new(None)),
+      connected: Arc::new(AtomicBool::new(true))
+    }
+  }
+
+  /**
+   * Simulate scooter going out of range. Writes fail and notifications stop until connect is called
+   */
+  pub fn disconnect(&self) {
+    self.connected.store(false, Ordering::SeqCst);
+    *self.subscriber.lock().unwrap() = None;
+  }
+
+  pub fn connect(&self) {
+    self.connected.store(true, Ordering::SeqCst);
+  }
+
+  pub fn is_connected(&self) -> bool {
+    self.connected.load(Ordering::SeqCst)
+  }
+
+  /**
+   * Access simulated scooter, for example to change values in its register table
+   */
+  pub fn scooter(&self) -> MutexGuard<'_, SimulatedScooter> {
+    self.scooter.lock().expect("Simulated scooter lock is poisoned")
+  }
+}
+
+#[async_trait]
+impl ScooterTransport for SimulatorTransport {
+  async fn notifications(&self) -> Result<NotificationStream> {
+    if !self.is_connected() {
+      return Err(TransportError::NotConnected.into())
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    *self.subscriber.lock().unwrap() = Some(tx);
+
+    Ok(Box::pin(UnboundedReceiverStream::new(rx)))
+  }
+
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()> {
+    if !self.is_connected() {
+      return Err(TransportError::NotConnected.into())
+    }
+
+    let notifications = self.scooter().handle(reg, data);
+    let subscriber = self.subscriber.lock().unwrap();
+
+    if let Some(tx) = subscriber.as_ref() {
+      for notification in notifications {
+        tx.send(notification)
+          .map_err(|_| anyhow!("Notifications stream for simulated scooter is closed"))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  async fn unsubscribe(&self) -> Result<()> {
+    *self.subscriber.lock().unwrap() = None;
+    Ok(())
+  }
+}
@@ -0,0 +1,220 @@
+use crate::scooter::Scooter;
+use crate::session::{MiSession, is_session_lost};
+use crate::transport::ScooterTransport;
+
+use std::time::Duration;
+use async_trait::async_trait;
+use btleplug::platform::Peripheral;
+use futures::future::BoxFuture;
+use tokio::sync::broadcast;
+use tokio::time::{self, error::Elapsed};
+use anyhow::{Result, anyhow};
+
+/**
+ * Anything that can bring back logged in session after connection was lost
+ */
+#[async_trait]
+pub trait Reconnect<T: ScooterTransport>: Send + Sync {
+  /**
+   * Connect again and login, returning fresh session
+   */
+  async fn reconnect(&self) -> Result<MiSession<T>>;
+
+  /**
+   * Return false when you already know that link is down, so supervisor does not wait for timeouts
+   */
+  fn is_connected(&self) -> bool {
+    true
+  }
+}
+
+#[async_trait]
+impl Reconnect<Peripheral> for Scooter {
+  async fn reconnect(&self) -> Result<MiSession<Peripheral>> {
+    Ok(Scooter::reconnect(self).await?)
+  }
+
+  fn is_connected(&self) -> bool {
+    Scooter::is_connected(self)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+  Connected,
+  /**
+   * Link was lost, or scooter stopped answering
+   */
+  Disconnected,
+  Reconnecting { attempt: u32, delay: Duration },
+  ReconnectFailed { attempt: u32, reason: String },
+  /**
+   * Supervisor run out of reconnect attempts
+   */
+  GaveUp
+}
+
+#[derive(Clone, Debug)]
+pub struct SupervisorConfig {
+  /**
+   * How many timeouts in a row mean that scooter is gone
+   */
+  pub max_timeouts: u32,
+  /**
+   * Delay before first reconnect attempt, doubled after each failed attempt
+   */
+  pub initial_backoff: Duration,
+  pub max_backoff: Duration,
+  /**
+   * Give up after this many failed reconnects, None means never give up
+   */
+  pub max_reconnects: Option<u32>
+}
+
+impl Default for SupervisorConfig {
+  fn default() -> Self {
+    Self {
+      max_timeouts: 2,
+      initial_backoff: Duration::from_secs(1),
+      max_backoff: Duration::from_secs(30),
+      max_reconnects: None
+    }
+  }
+}
+
+/**
+ * Wraps MiSession and brings it back when bluetooth link drops. Commands interrupted by disconnect are sent again after login.
+ *
+ * ```no_run
+ * # async fn run(scooter: m365::Scooter, session: m365::MiSession) -> anyhow::Result<()> {
+ * use m365::{SupervisedSession, SupervisorConfig};
+ *
+ * let mut supervised = SupervisedSession::new(scooter, session, SupervisorConfig::default());
+ * let motor_info = supervised.run(|session| Box::pin(session.motor_info())).await?;
+ * # Ok(())
+ * # }
+ * ```
+ */
+pub struct SupervisedSession<T: ScooterTransport, R: Reconnect<T>> {
+  connector: R,
+  session: Option<MiSession<T>>,
+  config: SupervisorConfig,
+  events: broadcast::Sender<SessionEvent>,
+  timeouts: u32
+}
+
+impl<T: ScooterTransport, R: Reconnect<T>> SupervisedSession<T, R> {
+  pub fn new(connector: R, session: MiSession<T>, config: SupervisorConfig) -> Self {
+    let (events, _) = broadcast::channel(32);
+
+    Self {
+      connector,
+      session: Some(session),
+      config,
+      events,
+      timeouts: 0
+    }
+  }
+
+  /**
+   * Receive state changes of supervised session
+   */
+  pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+    self.events.subscribe()
+  }
+
+  pub fn connector(&self) -> &R {
+    &self.connector
+  }
+
+  /**
+   * Run command on session. If link is lost while command runs, session is restored and command is sent again.
+   * Errors not related to connection are returned right away.
+   */
+  pub async fn run<V, F>(&mut self, mut command: F) -> Result<V>
+  where
+    F: for<'a> FnMut(&'a mut MiSession<T>) -> BoxFuture<'a, Result<V>>
+  {
+    loop {
+      if !self.connector.is_connected() && self.session.is_some() {
+        self.lost_connection();
+      }
+
+      if self.session.is_none() {
+        self.restore().await?;
+      }
+
+      let session = self.session.as_mut().expect("Session was just restored");
+      match command(session).await {
+        Ok(value) => {
+          self.timeouts = 0;
+          return Ok(value)
+        },
+
+        Err(error) if is_timeout(&error) => {
+          self.timeouts += 1;
+          tracing::warn!("Scooter did not answer in time ({} in a row)", self.timeouts);
+
+          if self.timeouts >= self.config.max_timeouts {
+            self.lost_connection();
+          } else {
+            return Err(error)
+          }
+        },
+
+        Err(error) if is_session_lost(&error) => {
+          tracing::warn!("Lost session with scooter: {}", error);
+          self.lost_connection();
+        },
+
+        Err(error) => return Err(error)
+      }
+    }
+  }
+
+  fn emit(&self, event: SessionEvent) {
+    // Nobody listening is fine
+    let _ = self.events.send(event);
+  }
+
+  fn lost_connection(&mut self) {
+    self.session = None;
+    self.timeouts = 0;
+    self.emit(SessionEvent::Disconnected);
+  }
+
+  async fn restore(&mut self) -> Result<()> {
+    let mut attempt = 0;
+    let mut delay = self.config.initial_backoff;
+
+    loop {
+      attempt += 1;
+      self.emit(SessionEvent::Reconnecting { attempt, delay });
+      time::sleep(delay).await;
+
+      match self.connector.reconnect().await {
+        Ok(session) => {
+          self.session = Some(session);
+          self.emit(SessionEvent::Connected);
+          return Ok(())
+        },
+
+        Err(error) => {
+          tracing::warn!("Reconnect attempt {} failed: {}", attempt, error);
+          self.emit(SessionEvent::ReconnectFailed { attempt, reason: error.to_string() });
+
+          if self.config.max_reconnects.is_some_and(|max| attempt >= max) {
+            self.emit(SessionEvent::GaveUp);
+            return Err(anyhow!("Could not reconnect after {} attempts: {}", attempt, error))
+          }
+
+          delay = (delay * 2).min(self.config.max_backoff);
+        }
+      }
+    }
+  }
+}
+
+fn is_timeout(error: &anyhow::Error) -> bool {
+  error.chain().any(|cause| cause.is::<Elapsed>())
+}
@@ -0,0 +1,125 @@
+use crate::consts::Registers;
+use uuid::Uuid;
+use futures::Stream;
+use std::pin::Pin;
+use async_trait::async_trait;
+use btleplug::platform::Peripheral;
+use btleplug::api::{Peripheral as BlePeripheral, Characteristic, WriteType, ValueNotification};
+use anyhow::{Context, Result, anyhow};
+use thiserror::Error;
+
+pub type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+#[derive(Error, Debug)]
+pub enum TransportError {
+  #[error("Scooter is not connected")]
+  NotConnected,
+  #[error("Notifications stream was closed")]
+  StreamClosed
+}
+
+/**
+ * Everything MiProtocol needs from the wire: writing bytes without response to one of the scooter registers
+ * and a stream of notifications coming back. Bluetooth peripheral implements it out of the box, but you can
+ * implement it for in memory channels, serial bridges or recorded captures.
+ */
+#[async_trait]
+pub trait ScooterTransport: Clone + Send + Sync {
+  /**
+   * Prepare channels and subscribe for notifications on AVDTP, UPNP and RX registers
+   */
+  async fn notifications(&self) -> Result<NotificationStream>;
+
+  /**
+   * Write bytes without response to register
+   */
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()>;
+
+  /**
+   * Stop receiving notifications
+   */
+  async fn unsubscribe(&self) -> Result<()>;
+}
+
+#[async_trait]
+impl ScooterTransport for Peripheral {
+  async fn notifications(&self) -> Result<NotificationStream> {
+    setup_channels(self).await?;
+
+    BlePeripheral::notifications(self).await
+      .with_context(|| "Could not load notifications stream")
+  }
+
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()> {
+    let channel = reg_to_channel(self, reg)?;
+
+    BlePeripheral::write(self, &channel, data, WriteType::WithoutResponse).await
+      .with_context(|| format!("Could not write data to {:?}", reg))?;
+
+    Ok(())
+  }
+
+  async fn unsubscribe(&self) -> Result<()> {
+    for reg in [Registers::AVDTP, Registers::UPNP, Registers::RX] {
+      let channel = reg_to_channel(self, &reg)?;
+      BlePeripheral::unsubscribe(self, &channel).await?;
+    }
+
+    Ok(())
+  }
+}
+
+fn reg_to_channel(device: &Peripheral, reg: &Registers) -> Result<Characteristic> {
+  let service_uuid = match reg {
+    Registers::RX | Registers::TX => Registers::UART.to_uuid(),
+    Registers::AVDTP | Registers::UPNP => Registers::AUTH.to_uuid(),
+    _ => return Err(anyhow!("{:?} is a service, not a characteristic", reg))
+  };
+
+  lookup_characteristic(device, service_uuid, reg.to_uuid())
+}
+
+fn lookup_characteristic(device : &Peripheral, service_uuid: Uuid, char_uuid: Uuid) -> Result<Characteristic> {
+  for ch in device.characteristics() {
+    if ch.uuid == char_uuid && ch.service_uuid == service_uuid {
+      return Ok(ch)
+    }
+  }
+
+  Err(anyhow!("Could not find characteristic: {}", char_uuid))
+}
+
+fn find_characteristic(device : &Peripheral, service_uuid: Uuid, char_uuid: Uuid) -> Result<Characteristic> {
+  let ch = lookup_characteristic(device, service_uuid, char_uuid)?;
+  tracing::debug!("Found Characteristic: {:?}", ch);
+  Ok(ch)
+}
+
+async fn setup_channels(device : &Peripheral) -> Result<()> {
+  device.discover_services().await
+    .with_context(|| "Could not enable discovering devices")?;
+
+  // Auth channels
+  tracing::debug!("Setting up AUTH channels");
+  let avdtp = find_characteristic(device, Registers::AUTH.to_uuid(), Registers::AVDTP.to_uuid())?;
+  let upnp = find_characteristic(device, Registers::AUTH.to_uuid(), Registers::UPNP.to_uuid())?;
+
+  // UART channels
+  tracing::debug!("Setting up UART channels");
+  find_characteristic(device, Registers::UART.to_uuid(), Registers::TX.to_uuid())?;
+  let rx = find_characteristic(device, Registers::UART.to_uuid(), Registers::RX.to_uuid())?;
+
+  tracing::debug!("Enabling notify for AVDTP");
+  device.subscribe(&avdtp).await
+    .with_context(|| "Could not subscribe to scooter AVDTP notifications")?;
+
+  tracing::debug!("Enabling notify for UPNP");
+  device.subscribe(&upnp).await
+    .with_context(|| "Could not subscribe to scooter UPNP notifications")?;
+
+  tracing::debug!("Enabling notify for RX");
+  device.subscribe(&rx).await
+    .with_context(|| "Could not subscribe to scooter RX notifications")?;
+
+  Ok(())
+}
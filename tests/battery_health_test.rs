@@ -0,0 +1,105 @@
+mod common;
+
+use m365::{
+  BatteryInfo,
+  BatteryPack,
+  BatteryHealthReport,
+  HealthThresholds,
+  HealthIssue,
+  ManufactureDate,
+  FirmwareVersion,
+  Severity
+};
+use m365::simulator::Bank;
+
+fn info(temperature: u8) -> BatteryInfo {
+  BatteryInfo {
+    capacity: 7000,
+    percent: 90,
+    current: 0.0,
+    voltage: 40.0,
+    temperature_1: temperature,
+    temperature_2: temperature
+  }
+}
+
+fn pack(full_charge_capacity: u16) -> BatteryPack {
+  BatteryPack {
+    serial: "3LABATTDECAMIL".to_owned(),
+    firmware_version: FirmwareVersion::from(0x0115),
+    design_capacity: 7800,
+    full_charge_capacity,
+    cycles: 120,
+    charge_count: 300,
+    manufacture_date: ManufactureDate::from(0x22A2)
+  }
+}
+
+#[test]
+fn it_reports_healthy_battery() {
+  let cells = [4.01; 10];
+  let report = BatteryHealthReport::analyze(&cells, &info(45), Some(&pack(7600)), &HealthThresholds::default());
+
+  assert_eq!(report.severity(), Severity::Ok);
+  assert_eq!(report.cell_count, 10);
+  assert_eq!(report.temperatures, vec![25, 25]);
+  assert!(report.findings.is_empty());
+}
+
+#[test]
+fn it_finds_weak_cell_and_imbalance() {
+  let mut cells = [4.01; 10];
+  cells[3] = 3.90;
+
+  let report = BatteryHealthReport::analyze(&cells, &info(45), None, &HealthThresholds::default());
+
+  assert_eq!(report.severity(), Severity::Warning);
+  assert_eq!(report.weak_cells(), vec![3]);
+  assert!((report.cell_imbalance - 0.11).abs() < 0.001);
+  assert!(report.findings.iter().any(|finding| matches!(finding.issue, HealthIssue::CellImbalance { .. })));
+}
+
+#[test]
+fn it_reports_dead_cell_at_its_position_in_pack() {
+  let mut cells = [4.01; 10];
+  cells[2] = 3.90;
+  cells[9] = 0.0;
+
+  let report = BatteryHealthReport::analyze(&cells, &info(45), None, &HealthThresholds::default());
+
+  assert_eq!(report.severity(), Severity::Critical);
+  assert_eq!(report.cell_count, 10);
+  assert_eq!(report.weak_cells(), vec![2, 9]);
+  assert!(report.findings.iter().any(|finding| {
+    finding.severity == Severity::Critical && finding.issue == HealthIssue::WeakCell { index: 9, voltage: 0.0 }
+  }));
+
+  // Dead cell is reported once, imbalance only covers live cells
+  assert!((report.min_cell_voltage - 3.90).abs() < 0.001);
+  assert!((report.cell_imbalance - 0.11).abs() < 0.001);
+  assert!(!report.findings.iter().any(|finding| {
+    finding.severity == Severity::Critical && matches!(finding.issue, HealthIssue::CellImbalance { .. })
+  }));
+}
+
+#[test]
+fn it_flags_capacity_fade_and_temperature() {
+  let cells = [3.8; 10];
+  let report = BatteryHealthReport::analyze(&cells, &info(85), Some(&pack(4000)), &HealthThresholds::default());
+
+  assert_eq!(report.severity(), Severity::Critical);
+  assert!((report.state_of_health.unwrap() - 51.28).abs() < 0.01);
+  assert!(report.findings.iter().any(|finding| finding.issue == HealthIssue::Temperature { sensor: 0, celsius: 65 }));
+}
+
+#[tokio::test]
+async fn it_checks_battery_health_of_simulated_scooter() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Battery, 0x45, 3500);
+
+  let report = session.battery_health().await.unwrap();
+  assert_eq!(report.cell_count, 10);
+  assert!((report.max_cell_voltage - 3.676).abs() < 0.001);
+  assert_eq!(report.weak_cells(), vec![5]);
+  assert_eq!(report.severity(), Severity::Critical);
+}
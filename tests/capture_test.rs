@@ -0,0 +1,84 @@
+mod common;
+
+use m365::{MiSession, ScooterTransport};
+use m365::consts::{Registers, MiCommands};
+use m365::capture::{Capture, CaptureDirection, CaptureTransport, ReplayTransport, ReplayError};
+use m365::simulator::{SimulatedScooter, SimulatorTransport};
+
+use std::time::Duration;
+
+async fn record() -> Capture {
+  let transport = CaptureTransport::new(SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN)));
+  let mut session = common::login(&transport).await;
+  transport.store_keys(session.keychain());
+
+  session.motor_info().await.unwrap();
+  session.battery_info().await.unwrap();
+
+  transport.capture()
+}
+
+#[tokio::test]
+async fn it_records_writes_and_notifications() {
+  let capture = record().await;
+
+  assert!(capture.keys.is_some());
+  assert!(capture.events.iter().any(|event| event.direction == CaptureDirection::Write));
+  assert!(capture.events.iter().any(|event| event.direction == CaptureDirection::Notification));
+  assert!(capture.events.windows(2).all(|pair| pair[0].timestamp_ms <= pair[1].timestamp_ms));
+
+  let json = serde_json::to_string(&capture).unwrap();
+  assert_eq!(serde_json::from_str::<Capture>(&json).unwrap(), capture);
+}
+
+#[tokio::test]
+async fn it_replays_session_from_capture() {
+  let capture = record().await.uart_only();
+  let keys = capture.keys.as_ref().unwrap().keychain().unwrap();
+
+  let transport = ReplayTransport::new(capture);
+  let mut session = MiSession::new(&transport, &keys).await.unwrap();
+
+  assert_eq!(session.motor_info().await.unwrap().total_distance_m, 1306083);
+  assert_eq!(session.battery_info().await.unwrap().capacity, 7417);
+  assert_eq!(transport.remaining(), 0);
+
+  assert!(session.motor_info().await.is_err());
+}
+
+#[tokio::test]
+async fn it_refuses_replay_of_different_command() {
+  let capture = record().await.uart_only();
+  let keys = capture.keys.as_ref().unwrap().keychain().unwrap();
+
+  let transport = ReplayTransport::new(capture);
+  let mut session = MiSession::new(&transport, &keys).await.unwrap();
+
+  let error = session.battery_info().await.unwrap_err();
+  assert!(error.chain().any(|cause| matches!(cause.downcast_ref::<ReplayError>(), Some(ReplayError::UnexpectedCommand { .. }))), "{}", error);
+}
+
+#[tokio::test]
+async fn it_records_only_writes_that_went_through() {
+  let simulator = SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN));
+  let transport = CaptureTransport::new(simulator.clone());
+  let mut session = common::login(&transport).await;
+  let recorded = transport.capture().events.len();
+
+  simulator.disconnect();
+  assert!(session.motor_info().await.is_err());
+  assert_eq!(transport.capture().events.len(), recorded);
+}
+
+#[tokio::test]
+async fn it_records_notifications_when_they_arrive() {
+  let transport = CaptureTransport::new(SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN)));
+  let _unread = transport.notifications().await.unwrap();
+
+  transport.write(&Registers::UPNP, &MiCommands::CMD_LOGIN.to_bytes()).await.unwrap();
+  transport.write(&Registers::AVDTP, &MiCommands::CMD_SEND_KEY.to_bytes()).await.unwrap();
+  tokio::time::sleep(Duration::from_millis(10)).await;
+
+  let capture = transport.capture();
+  assert!(capture.events.iter().any(|event| event.direction == CaptureDirection::Notification));
+}
@@ -0,0 +1,27 @@
+#![allow(dead_code)]
+
+use m365::{AuthToken, LoginRequest, MiSession, ScooterTransport};
+use m365::simulator::{SimulatedScooter, SimulatorTransport};
+
+/**
+ * Token that simulated scooter is paired with
+ */
+pub const TOKEN : AuthToken = [7; 12];
+
+/**
+ * Login over any transport with TOKEN
+ */
+pub async fn login<T: ScooterTransport>(transport: &T) -> MiSession<T> {
+  LoginRequest::new(transport, &TOKEN).await.unwrap()
+    .start().await.unwrap()
+}
+
+/**
+ * Simulated scooter paired with TOKEN and session logged in to it
+ */
+pub async fn simulated_session() -> (SimulatorTransport, MiSession<SimulatorTransport>) {
+  let transport = SimulatorTransport::new(SimulatedScooter::with_token(&TOKEN));
+  let session = login(&transport).await;
+
+  (transport, session)
+}
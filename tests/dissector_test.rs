@@ -0,0 +1,113 @@
+mod common;
+
+use m365::{
+  Direction,
+  ReadWrite
+};
+use m365::capture::{CaptureDirection, CaptureTransport, hex_bytes};
+use m365::dissector::{dissect_capture, dissect_frame, FrameAssembler};
+use m365::mi_crypto::encrypt_uart;
+use m365::simulator::{SimulatedScooter, SimulatorTransport};
+
+#[tokio::test]
+async fn it_dissects_captured_session() {
+  let transport = CaptureTransport::new(SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN)));
+  let mut session = common::login(&transport).await;
+  let keys = session.keychain().clone();
+
+  session.distance_left().await.unwrap();
+  session.set_cruise(true).await.unwrap();
+
+  let frames = dissect_capture(&transport.capture(), &keys);
+  assert!(frames.iter().all(|frame| frame.crc_valid && frame.error.is_none()), "{:#?}", frames);
+
+  let request = &frames[0];
+  assert_eq!(request.origin, CaptureDirection::Write);
+  assert_eq!(request.direction, Some(Direction::MasterToMotor));
+  assert_eq!(request.read_write, Some(ReadWrite::Read));
+  assert_eq!(request.name, Some("DistanceLeft"));
+  assert_eq!(request.value.as_deref(), Some("read 2 bytes"));
+
+  let reply = &frames[1];
+  assert_eq!(reply.origin, CaptureDirection::Notification);
+  assert_eq!(reply.direction, Some(Direction::MotorToMaster));
+  assert_eq!(reply.value.as_deref(), Some("28.35"));
+
+  let write = &frames[2];
+  assert_eq!(write.read_write, Some(ReadWrite::Write));
+  assert_eq!(write.name, Some("Cruise"));
+  assert_eq!(write.value.as_deref(), Some("true"));
+  assert!(write.to_string().contains("MasterToMotor Write 0x7c Cruise: true"), "{}", write);
+}
+
+#[test]
+fn it_joins_chunks_and_flags_bad_crc() {
+  let keys = m365::mi_crypto::LoginKeychain {
+    dev: m365::mi_crypto::EncryptionKey { key: [1; 16], iv: [2; 4] },
+    app: m365::mi_crypto::EncryptionKey { key: [3; 16], iv: [4; 4] }
+  };
+  let frame = encrypt_uart(&keys.app, &[0x03, 0x22, 0x01, 0x32, 0x02], 5, None);
+
+  let mut assembler = FrameAssembler::new();
+  assert!(assembler.push(&[0x00, 0x01]).is_empty());
+  assert!(assembler.push(&frame[..10]).is_empty());
+  let frames = assembler.push(&frame[10..]);
+  assert_eq!(frames, vec![frame.clone()]);
+
+  let dissected = dissect_frame(&frame, CaptureDirection::Write, &keys);
+  assert!(dissected.crc_valid);
+  assert_eq!(dissected.counter, Some(5));
+  assert_eq!(dissected.name, Some("BatteryPercent"));
+
+  let mut broken = frame.clone();
+  let last = broken.len() - 1;
+  broken[last] ^= 0xFF;
+  assert!(!dissect_frame(&broken, CaptureDirection::Write, &keys).crc_valid);
+
+  let wrong_key = dissect_frame(&frame, CaptureDirection::Notification, &keys);
+  assert!(wrong_key.error.is_some());
+}
+
+#[test]
+fn it_decodes_temperature_and_battery_pack() {
+  let keys = m365::mi_crypto::LoginKeychain {
+    dev: m365::mi_crypto::EncryptionKey { key: [1; 16], iv: [2; 4] },
+    app: m365::mi_crypto::EncryptionKey { key: [3; 16], iv: [4; 4] }
+  };
+  let reply = |msg: &[u8]| dissect_frame(&encrypt_uart(&keys.dev, msg, 1, None), CaptureDirection::Notification, &keys);
+
+  let temperature = reply(&[0x04, 0x23, 0x01, 0x3E, 0x18, 0x01]);
+  assert_eq!(temperature.name, Some("FrameTemperature"));
+  assert_eq!(temperature.value.as_deref(), Some("28.0"));
+
+  let cycles = reply(&[0x06, 0x25, 0x01, 0x1B, 0x01, 0x00, 0x03, 0x00]);
+  assert_eq!(cycles.name, Some("BatteryCycles"));
+  assert_eq!(cycles.value.as_deref(), Some("(1, 3)"));
+
+  let mut pack = vec![0x14, 0x25, 0x01, 0x10];
+  pack.extend_from_slice(b"3LABATTDECAMIL");
+  pack.extend_from_slice(&[0x15, 0x01, 0x78, 0x1E]);
+  let pack = reply(&pack);
+  assert_eq!(pack.name, Some("BatteryPack"));
+  assert_eq!(pack.value.as_deref(), Some("BatteryPack { serial: \"3LABATTDECAMIL\", firmware_version: 1.1.5, design_capacity: 7800 }"));
+}
+
+#[test]
+fn it_survives_long_frame_of_ff() {
+  let keys = m365::mi_crypto::LoginKeychain {
+    dev: m365::mi_crypto::EncryptionKey { key: [1; 16], iv: [2; 4] },
+    app: m365::mi_crypto::EncryptionKey { key: [3; 16], iv: [4; 4] }
+  };
+
+  let dissected = dissect_frame(&[0xFF; 300], CaptureDirection::Notification, &keys);
+  assert!(!dissected.crc_valid);
+  assert!(dissected.error.is_some());
+}
+
+#[test]
+fn it_rejects_invalid_hex() {
+  assert_eq!(hex_bytes::decode("55ab03"), Some(vec![0x55, 0xAB, 0x03]));
+  assert_eq!(hex_bytes::decode("0x55"), None);
+  assert_eq!(hex_bytes::decode("+5"), None);
+  assert_eq!(hex_bytes::decode("55a"), None);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use m365::{
+  ScooterModel,
+  Capabilities,
+  FirmwareVersion,
+  Feature,
+  SessionError
+};
+use m365::simulator::Bank;
+
+#[test]
+fn it_detects_model_from_serial_firmware_and_battery() {
+  let unknown_serial = "99999/00000001";
+
+  // Serial prefix wins over everything else
+  assert_eq!(ScooterModel::detect("16133/00123456", FirmwareVersion::from(0x0319), 5100), ScooterModel::M365);
+
+  // Firmware generation, battery only picks one of pair
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0015), 7650), ScooterModel::Mi3);
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0319), 7650), ScooterModel::OneS);
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0248), 12800), ScooterModel::Pro2);
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0155), 12800), ScooterModel::Pro);
+
+  // Aftermarket battery does not change model with known firmware
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0134), 15000), ScooterModel::M365);
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0319), 15000), ScooterModel::OneS);
+
+  // Unknown firmware falls back to battery
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0F00), 5100), ScooterModel::Essential);
+  assert_eq!(ScooterModel::detect(unknown_serial, FirmwareVersion::from(0x0F00), 1234), ScooterModel::Unknown);
+}
+
+#[test]
+fn it_supports_shared_settings_on_every_model() {
+  assert_eq!(ScooterModel::Essential.capabilities().max_speed_kmh, 20);
+
+  for model in [ScooterModel::M365, ScooterModel::Essential, ScooterModel::OneS, ScooterModel::Pro, ScooterModel::Pro2, ScooterModel::Mi3] {
+    let capabilities = model.capabilities();
+    for feature in [Feature::Cruise, Feature::TailLight, Feature::Kers, Feature::Lock, Feature::SpeedLimit] {
+      assert!(capabilities.supports(feature), "{} should support {:?}", model, feature);
+    }
+  }
+}
+
+#[tokio::test]
+async fn it_detects_model_after_login() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Battery, 0x18, 12800);
+
+  assert_eq!(session.model(), ScooterModel::Unknown);
+
+  assert_eq!(session.detect_model().await.unwrap(), ScooterModel::Pro);
+  assert_eq!(session.capabilities().design_capacity, 12800);
+
+  transport.scooter().memory.write_u16(Bank::Motor, 0x1A, 0x0248);
+  assert_eq!(session.detect_model().await.unwrap(), ScooterModel::Pro2);
+}
+
+#[tokio::test]
+async fn it_refuses_settings_that_model_does_not_support() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  session.set_model(ScooterModel::OneS);
+  session.set_capabilities(Capabilities { features: vec![Feature::TailLight], ..session.capabilities().clone() });
+
+  let error = session.set_cruise(true).await.unwrap_err();
+  assert!(matches!(error.downcast_ref::<SessionError>(), Some(SessionError::Unsupported { feature: Feature::Cruise, .. })));
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x7C), 0);
+}
@@ -4,7 +4,9 @@ use std::time::Duration;
 use m365::{
   Payload,
   MotorInfo,
-  BatteryInfo
+  BatteryInfo,
+  ErrorCode,
+  Phase
 };
 
 #[test]
@@ -35,3 +37,15 @@ fn it_transform_payload_into_battery_info() {
   assert_eq!(battery.temperature_1, 45);
   assert_eq!(battery.temperature_2, 45);
 }
+
+#[test]
+fn it_maps_motor_phase_errors_both_ways() {
+  for (code, phase) in [(11, Phase::A), (12, Phase::B), (13, Phase::C)] {
+    let error = ErrorCode::from(code);
+
+    assert_eq!(error, ErrorCode::MotorPhaseCurrent(phase));
+    assert_eq!(error.code(), code);
+  }
+
+  assert_eq!(ErrorCode::from(12).to_string(), "Error 12: abnormal current in motor phase B");
+}
@@ -0,0 +1,109 @@
+use std::env;
+use btleplug::api::BDAddr;
+use m365::{
+  Pairing,
+  PairingKey,
+  PairingStore,
+  PairingError,
+  FilePairingStore,
+  MemoryPairingStore
+};
+
+fn address() -> BDAddr {
+  BDAddr::from_str_delim("D5:01:45:37:ED:FD").unwrap()
+}
+
+#[tokio::test]
+async fn it_finds_pairing_by_address_and_serial() {
+  let store = MemoryPairingStore::new();
+  let mut pairing = Pairing::new(address(), [1; 12]);
+  pairing.serial = Some("26354/00467353".to_owned());
+  store.save(pairing.clone()).await.unwrap();
+
+  let by_address = store.get(&PairingKey::Address(address())).await.unwrap();
+  let by_serial = store.get(&PairingKey::Serial("26354/00467353".to_owned())).await.unwrap();
+
+  assert_eq!(by_address, Some(pairing.clone()));
+  assert_eq!(by_serial, Some(pairing));
+}
+
+#[tokio::test]
+async fn it_replaces_pairing_for_the_same_scooter() {
+  let store = MemoryPairingStore::new();
+  store.save(Pairing::new(address(), [1; 12])).await.unwrap();
+  store.save(Pairing::new(address(), [2; 12])).await.unwrap();
+
+  let pairings = store.list().await.unwrap();
+  assert_eq!(pairings.len(), 1);
+  assert_eq!(pairings[0].token, [2; 12]);
+}
+
+#[tokio::test]
+async fn it_persists_pairings_in_file() {
+  let path = env::temp_dir().join(format!("m365-pairings-{}.json", uuid::Uuid::new_v4()));
+  let store = FilePairingStore::new(&path);
+
+  let mut pairing = Pairing::new(address(), [3; 12]);
+  pairing.device_name = Some("MIScooter7353".to_owned());
+  pairing.firmware_version = Some("1.3.4".to_owned());
+  store.save(pairing.clone()).await.unwrap();
+
+  let reopened = FilePairingStore::new(&path);
+  assert_eq!(reopened.get(&PairingKey::Address(address())).await.unwrap(), Some(pairing));
+  assert_eq!(reopened.remove(&PairingKey::Address(address())).await.unwrap().map(|p| p.token), Some([3; 12]));
+  assert_eq!(reopened.list().await.unwrap(), vec![]);
+
+  std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn it_refuses_unknown_file_version() {
+  let path = env::temp_dir().join(format!("m365-pairings-{}.json", uuid::Uuid::new_v4()));
+  std::fs::write(&path, r#"{ "version": 99, "pairings": [] }"#).unwrap();
+
+  let store = FilePairingStore::new(&path);
+  let result = store.list().await;
+  std::fs::remove_file(&path).unwrap();
+
+  assert!(matches!(result, Err(PairingError::UnsupportedVersion(99))));
+}
+
+#[tokio::test]
+async fn it_keeps_updates_from_stores_sharing_a_file() {
+  let path = env::temp_dir().join(format!("m365-pairings-{}.json", uuid::Uuid::new_v4()));
+
+  let saves = (0..16u8).map(|index| {
+    let store = FilePairingStore::new(&path);
+    let address = BDAddr::from([0xD5, 0x01, 0x45, 0x37, 0xED, index]);
+    tokio::spawn(async move { store.save(Pairing::new(address, [index; 12])).await })
+  });
+
+  for save in futures::future::join_all(saves).await {
+    save.unwrap().unwrap();
+  }
+
+  let pairings = FilePairingStore::new(&path).list().await.unwrap();
+  std::fs::remove_file(&path).unwrap();
+
+  assert_eq!(pairings.len(), 16);
+}
+
+
+#[tokio::test]
+async fn it_imports_legacy_token_file() {
+  let path = env::temp_dir().join(format!("m365-pairings-{}.json", uuid::Uuid::new_v4()));
+  let token_path = env::temp_dir().join(format!("m365-token-{}", uuid::Uuid::new_v4()));
+  std::fs::write(&token_path, [5; 12]).unwrap();
+
+  let store = FilePairingStore::new(&path);
+  let imported = store.import_token_file(&token_path, address()).await.unwrap();
+  let missing = store.import_token_file(env::temp_dir().join("m365-missing-token"), address()).await.unwrap();
+  let stored = store.get(&PairingKey::Address(address())).await.unwrap();
+
+  std::fs::remove_file(&path).unwrap();
+  std::fs::remove_file(&token_path).unwrap();
+
+  assert_eq!(imported.map(|pairing| pairing.token), Some([5; 12]));
+  assert!(missing.is_none());
+  assert_eq!(stored.map(|pairing| pairing.token), Some([5; 12]));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use m365::{
+  TelemetryConfig,
+  RideRecorder,
+  RideFormat,
+  RideSample,
+  Ride,
+  RideError,
+  ScooterModel
+};
+use m365::simulator::Bank;
+
+use std::io::Cursor;
+use std::time::Duration;
+use futures::StreamExt;
+
+async fn record(format: RideFormat) -> Vec<u8> {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_model(ScooterModel::M365);
+
+  let header = session.ride_header().await.unwrap();
+  let mut recorder = RideRecorder::new(Vec::new(), format, &header).unwrap();
+
+  let config = TelemetryConfig {
+    motor_interval: Some(Duration::from_millis(10)),
+    cell_voltages_interval: Some(Duration::from_millis(10)),
+    ..TelemetryConfig::default()
+  };
+  let mut telemetry = Box::pin(session.telemetry_stream(config).unwrap());
+
+  recorder.record(&telemetry.next().await.unwrap().unwrap()).unwrap();
+  transport.scooter().memory.write_u32(Bank::Motor, 0xB7, 1307083);
+  recorder.record(&telemetry.next().await.unwrap().unwrap()).unwrap();
+
+  recorder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn it_records_and_reads_ndjson_ride() {
+  let bytes = record(RideFormat::Ndjson).await;
+  let ride = Ride::read(Cursor::new(bytes)).unwrap();
+
+  assert_eq!(ride.format, RideFormat::Ndjson);
+  assert_eq!(ride.header.serial, "26354/00467353");
+  assert_eq!(ride.header.model, ScooterModel::M365);
+  assert_eq!(ride.header.firmware.esc.to_string(), "1.3.4");
+  assert_eq!(ride.header.start_odometer_m, 1306083);
+  assert_eq!(ride.samples.len(), 2);
+  assert_eq!(ride.samples[0].cell_voltages.len(), 10);
+  assert_eq!(ride.distance_m(), 1000);
+}
+
+#[tokio::test]
+async fn it_records_and_reads_csv_ride() {
+  let bytes = record(RideFormat::Csv).await;
+  assert!(String::from_utf8_lossy(&bytes).starts_with("# {"));
+
+  let csv = Ride::read(Cursor::new(bytes)).unwrap();
+  let ndjson = Ride::read(Cursor::new(record(RideFormat::Ndjson).await)).unwrap();
+
+  assert_eq!(csv.format, RideFormat::Csv);
+  assert_eq!(csv.header.serial, ndjson.header.serial);
+  assert_eq!(csv.samples.len(), 2);
+  assert_eq!(csv.samples[1].total_distance_m, Some(1307083));
+  assert_eq!(csv.samples[1].cell_voltages, ndjson.samples[1].cell_voltages);
+  assert_eq!(csv.samples[1].battery_percent, Some(63));
+}
+
+#[test]
+fn it_keeps_missing_values_empty() {
+  let header = r#"{"version":1,"serial":"26354/00467353","model":"M365","firmware":{"esc":{"major":1,"minor":3,"patch":4},"ble":{"major":0,"minor":9,"patch":0},"bms":{"major":1,"minor":1,"patch":5}},"start_odometer_m":0,"started_at_ms":0}"#;
+  let sample = RideSample { timestamp_ms: 10, speed_kmh: Some(12.5), ..RideSample::default() };
+
+  let mut recorder = RideRecorder::new(Vec::new(), RideFormat::Csv, &serde_json::from_str(header).unwrap()).unwrap();
+  recorder.record_sample(&sample).unwrap();
+
+  let ride = Ride::read(Cursor::new(recorder.finish().unwrap())).unwrap();
+  assert_eq!(ride.samples, vec![sample]);
+}
+
+#[test]
+fn it_rejects_corrupted_cell_voltages() {
+  let header = r#"{"version":1,"serial":"26354/00467353","model":"M365","firmware":{"esc":{"major":1,"minor":3,"patch":4},"ble":{"major":0,"minor":9,"patch":0},"bms":{"major":1,"minor":1,"patch":5}},"start_odometer_m":0,"started_at_ms":0}"#;
+  let file = format!("{}\n{}\n", header, r#"{"timestamp_ms":10,"cell_voltages":"3.9;volts"}"#);
+
+  assert!(matches!(Ride::read(Cursor::new(file)), Err(RideError::Corrupted { line: 2, .. })));
+}
+
+#[test]
+fn it_rejects_unknown_version() {
+  let file = "{\"version\":99}\n";
+  assert!(matches!(Ride::read(Cursor::new(file)), Err(RideError::UnsupportedVersion(99))));
+  assert!(matches!(Ride::read(Cursor::new("")), Err(RideError::Empty)));
+}
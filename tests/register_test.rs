@@ -0,0 +1,52 @@
+mod common;
+
+use m365::{
+  Register,
+  Direction,
+  ReadWrite,
+  ScooterCommand,
+  SessionError,
+  registers
+};
+use m365::simulator::Bank;
+
+const SPEED_LIMIT : Register<u16> = Register::new(Direction::MasterToMotor, 0x73, 0x02, |payload| payload.pop_u16())
+  .with_encoder(|limit| limit.to_le_bytes().to_vec());
+
+const ESC_VERSION : Register<u16> = Register::new(Direction::MasterToMotor, 0x1A, 0x02, |payload| payload.pop_u16());
+
+#[tokio::test]
+async fn it_reads_and_writes_declared_registers() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Motor, 0x73, 20000);
+
+  assert_eq!(session.read_register(&ESC_VERSION).await.unwrap(), 0x0134);
+  assert_eq!(session.read_register(&SPEED_LIMIT).await.unwrap(), 20000);
+
+  session.write_register(&SPEED_LIMIT, &15000).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x73), 15000);
+  assert_eq!(session.read_register(&registers::BATTERY_VOLTAGE).await.unwrap(), 36.76);
+}
+
+#[test]
+fn it_refuses_to_write_register_without_encoder() {
+  assert!(!ESC_VERSION.is_writable());
+
+  let error = ESC_VERSION.write_command(&1).unwrap_err();
+  assert!(matches!(error.downcast_ref::<SessionError>(), Some(SessionError::ReadOnlyRegister(0x1A))));
+}
+
+#[tokio::test]
+async fn it_sends_raw_commands() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let response = session.request(&ScooterCommand::raw(0x22, 0x01, 0x17, vec![0x02])).await.unwrap();
+  assert_eq!(response.direction, Direction::BatteryToMaster);
+  assert_eq!(response.read_write, ReadWrite::Read);
+  assert_eq!(response.address, 0x17);
+  assert_eq!(response.data, vec![0x15, 0x01]);
+  assert_eq!(response.payload().pop_u16().unwrap(), 0x0115);
+
+  session.send(&ScooterCommand::raw(0x20, 0x03, 0x99, vec![0x34, 0x12])).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x99), 0x1234);
+}
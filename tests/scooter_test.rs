@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use btleplug::api::BDAddr;
+use m365::{
+  Pairing,
+  PairingStore,
+  MemoryPairingStore,
+  TokenSource,
+  ScooterError,
+  ConnectStage
+};
+
+#[tokio::test]
+async fn it_loads_token_from_pairing_store() {
+  let mac = BDAddr::from_str_delim("D5:01:45:37:ED:FD").unwrap();
+  let store = Arc::new(MemoryPairingStore::new());
+  store.save(Pairing::new(mac, [5; 12])).await.unwrap();
+
+  let source = TokenSource::from(store);
+  assert_eq!(source.token_for(&mac).await.unwrap(), [5; 12]);
+}
+
+#[tokio::test]
+async fn it_reports_missing_pairing_as_token_stage() {
+  let mac = BDAddr::from_str_delim("D5:01:45:37:ED:FD").unwrap();
+  let source = TokenSource::from(Arc::new(MemoryPairingStore::new()));
+
+  let error = source.token_for(&mac).await.unwrap_err();
+  assert!(matches!(error, ScooterError::NotPaired(_)));
+  assert_eq!(error.stage(), ConnectStage::Token);
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use m365::{
+  RegistrationRequest,
+  LoginRequest,
+  SessionError,
+  FirmwareVersion,
+  ErrorCode,
+  WorkMode,
+  Kers,
+  TailLight,
+  ScooterModel,
+  ScooterTransport
+};
+use std::time::Duration;
+use m365::consts::Registers;
+use m365::mi_crypto::encrypt_uart;
+use m365::simulator::{SimulatedScooter, SimulatorTransport, Bank};
+
+#[tokio::test]
+async fn it_registers_and_logs_in_to_simulated_scooter() {
+  let transport = SimulatorTransport::new(SimulatedScooter::new());
+
+  let mut registration = RegistrationRequest::new(&transport).await.unwrap();
+  let token = registration.start().await.unwrap();
+  assert_eq!(transport.scooter().token(), Some(token));
+
+  let mut login = LoginRequest::new(&transport, &token).await.unwrap();
+  let mut session = login.start().await.unwrap();
+  assert!(transport.scooter().is_logged_in());
+
+  let motor_info = session.motor_info().await.unwrap();
+  let battery_info = session.battery_info().await.unwrap();
+
+  assert_eq!(motor_info.battery_percent, battery_info.percent);
+  assert_eq!(motor_info.total_distance_m, 1306083);
+  assert_eq!(battery_info.capacity, 7417);
+  assert_eq!(session.serial_number().await.unwrap(), "26354/00467353");
+}
+
+#[tokio::test]
+async fn it_refuses_login_with_unknown_token() {
+  let transport = SimulatorTransport::new(SimulatedScooter::with_token(&[1; 12]));
+
+  let mut login = LoginRequest::new(&transport, &[2; 12]).await.unwrap();
+  assert!(login.start().await.is_err());
+  assert!(!transport.scooter().is_logged_in());
+}
+
+#[tokio::test]
+async fn it_reads_and_writes_simulated_registers() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Motor, 0xB4, 42);
+
+  assert_eq!(session.motor_info().await.unwrap().battery_percent, 42);
+
+  session.set_cruise(true).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x7C), 1);
+  assert!(session.is_cruise_on().await.unwrap());
+}
+
+#[tokio::test]
+async fn it_rejects_responses_with_reused_message_counter() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  session.motor_info().await.unwrap();
+  session.battery_info().await.unwrap();
+
+  transport.scooter().reset_message_counter();
+  let error = session.motor_info().await.unwrap_err();
+
+  match error.downcast_ref::<SessionError>() {
+    Some(SessionError::ReplayedFrame { last, received }) => {
+      assert_eq!(*last, 1);
+      assert_eq!(*received, 0);
+    },
+    _ => panic!("Expected replayed frame error, got: {}", error)
+  }
+}
+
+#[tokio::test]
+async fn it_drops_malformed_uart_frames() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let keys = session.keychain().clone();
+  transport.write(&Registers::TX, &encrypt_uart(&keys.app, &[0x00, 0x20], 100, None)).await.unwrap();
+  transport.write(&Registers::TX, &encrypt_uart(&keys.app, &[0x01, 0x20, 0x01], 101, None)).await.unwrap();
+
+  assert_eq!(session.motor_info().await.unwrap().total_distance_m, 1306083);
+}
+
+#[tokio::test]
+async fn it_drops_stale_and_unrelated_notifications() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  {
+    let mut scooter = transport.scooter();
+    scooter.queue_register_reply(Bank::Battery, 0x31, 0x0A);
+    scooter.queue_notification(Registers::UPNP, vec![0x00, 0x00, 0x01, 0x01]);
+  }
+
+  let motor_info = session.motor_info().await.unwrap();
+  assert_eq!(motor_info.total_distance_m, 1306083);
+  assert_eq!(session.serial_number().await.unwrap(), "26354/00467353");
+}
+
+#[tokio::test]
+async fn it_reads_firmware_versions() {
+  let (_, mut session) = common::simulated_session().await;
+
+  let versions = session.firmware_versions().await.unwrap();
+  assert_eq!(versions.esc, FirmwareVersion { major: 1, minor: 3, patch: 4 });
+  assert_eq!(versions.bms.to_string(), "1.1.5");
+  assert_eq!(versions.ble.to_string(), "0.9.0");
+
+  let general_info = format!("{:?}", session.general_info().await.unwrap());
+  assert!(general_info.contains("26354/00467353"), "{}", general_info);
+  assert!(general_info.contains("major: 1, minor: 3, patch: 4"), "{}", general_info);
+}
+
+#[tokio::test]
+async fn it_reads_trip_info() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Motor, 0x3A, 600);
+  transport.scooter().memory.write_u16(Bank::Motor, 0x3B, 3000);
+
+  transport.scooter().memory.write_u16(Bank::Motor, 0xB6, 15500);
+
+  let trip = session.trip_info().await.unwrap();
+  assert_eq!(trip.duration, Duration::from_secs(600));
+  assert_eq!(trip.distance_m, 3000);
+  assert!((trip.average_speed_kmh - 15.5).abs() < 0.01);
+}
+
+#[tokio::test]
+async fn it_reads_battery_pack_info() {
+  let (_, mut session) = common::simulated_session().await;
+
+  let pack = session.battery_pack_info().await.unwrap();
+  assert_eq!(pack.serial, "3LABATTDECAMIL");
+  assert_eq!(pack.firmware_version.to_string(), "1.1.5");
+  assert_eq!(pack.design_capacity, 7800);
+  assert_eq!(pack.full_charge_capacity, 7600);
+  assert_eq!(pack.cycles, 12);
+  assert_eq!(pack.charge_count, 45);
+  assert_eq!(pack.manufacture_date.to_string(), "2017-05-02");
+}
+
+#[tokio::test]
+async fn it_reads_variable_number_of_cells() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  assert_eq!(session.battery_cell_voltages().await.unwrap(), vec![3.676; 10]);
+
+  transport.scooter().memory.write_u16(Bank::Battery, 0x4A, 3650);
+  transport.scooter().memory.write_u16(Bank::Battery, 0x4B, 3640);
+  let cells = session.battery_cell_voltages().await.unwrap();
+  assert_eq!(cells.len(), 12);
+  assert_eq!(cells[11], 3.64);
+
+  assert_eq!(session.battery_cell_voltages_for(4).await.unwrap().len(), 4);
+  assert_eq!(session.battery_cell_voltages_for(200).await.unwrap().len(), 16);
+}
+
+#[tokio::test]
+async fn it_keeps_dead_cell_of_detected_model() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Battery, 0x49, 0);
+
+  session.set_model(ScooterModel::M365);
+
+  let cells = session.battery_cell_voltages().await.unwrap();
+  assert_eq!(cells.len(), 10);
+  assert_eq!(cells[9], 0.0);
+}
+
+#[tokio::test]
+async fn it_decodes_error_and_work_mode_from_motor_info() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let motor_info = session.motor_info().await.unwrap();
+  assert_eq!(motor_info.error, ErrorCode::None);
+  assert!(motor_info.warnings.is_empty());
+
+  {
+    let mut scooter = transport.scooter();
+    scooter.memory.write_u16(Bank::Motor, 0xB0, 14);
+    scooter.memory.write_u16(Bank::Motor, 0xB1, 0b101);
+    scooter.memory.write_u16(Bank::Motor, 0xB3, 1);
+  }
+
+  let motor_info = session.motor_info().await.unwrap();
+  assert_eq!(motor_info.error, ErrorCode::Throttle);
+  assert_eq!(motor_info.error.code(), 14);
+  assert_eq!(motor_info.warnings.active(), vec![0, 2]);
+  assert_eq!(motor_info.work_mode, WorkMode::Eco);
+  assert_eq!(motor_info.battery_percent, 63);
+}
+
+#[tokio::test]
+async fn it_changes_kers() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  assert_eq!(session.kers().await.unwrap(), Kers::Weak);
+
+  session.set_kers(Kers::Strong).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x7B), 2);
+  assert_eq!(session.kers().await.unwrap(), Kers::Strong);
+  assert!(session.set_kers(Kers::Unknown).await.is_err());
+
+  let supplementary = format!("{:?}", session.supplementary_info().await.unwrap());
+  assert!(supplementary.contains("kers: Strong"), "{}", supplementary);
+}
+
+#[tokio::test]
+async fn it_refuses_unknown_tail_light_without_writing() {
+  let (transport, mut session) = common::simulated_session().await;
+  transport.scooter().memory.write_u16(Bank::Motor, 0x7D, 2);
+
+  assert!(session.set_tail_light(TailLight::Unknown).await.is_err());
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x7D), 2);
+}
+
+#[tokio::test]
+async fn it_writes_setting_again_when_scooter_ignored_it() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(2, Duration::from_millis(1));
+
+  transport.scooter().ignore_writes(2);
+  session.set_tail_light(TailLight::Always).await.unwrap();
+  assert_eq!(session.tail_light().await.unwrap(), TailLight::Always);
+}
+
+#[tokio::test]
+async fn it_reports_setting_that_was_not_applied() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(1, Duration::from_millis(1));
+
+  transport.scooter().ignore_writes(2);
+  let err = session.set_cruise(true).await.unwrap_err();
+
+  match err.downcast_ref::<SessionError>() {
+    Some(SessionError::SettingNotApplied { register, expected, actual }) => {
+      assert_eq!(*register, 0x7C);
+      assert_eq!(expected, "true");
+      assert_eq!(actual, "false");
+    },
+    other => panic!("Unexpected error: {:?}", other)
+  }
+
+  assert!(!session.is_cruise_on().await.unwrap());
+}
+
+#[tokio::test]
+async fn it_locks_unlocks_and_powers_off() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(0, Duration::from_millis(1));
+
+  assert!(!session.is_locked().await.unwrap());
+
+  session.lock().await.unwrap();
+  assert!(session.is_locked().await.unwrap());
+  assert!(session.motor_info().await.unwrap().flags.is_locked());
+
+  session.unlock().await.unwrap();
+  assert!(!session.is_locked().await.unwrap());
+
+  session.power_off().await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x79), 1);
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use m365::{
+  ScooterModel,
+  SessionError,
+  SpeedProfile,
+  SpeedPolicy,
+  SpeedRule,
+  WorkMode
+};
+use m365::simulator::Bank;
+
+use std::time::Duration;
+
+fn profile(limit_kmh: f32) -> SpeedProfile {
+  SpeedProfile { limit_kmh, mode: WorkMode::Normal }
+}
+
+#[test]
+fn it_finds_limits_over_model_and_regional_maximum() {
+  let capabilities = ScooterModel::Essential.capabilities();
+  let policy = SpeedPolicy { regional_max_kmh: Some(18.0), strict: true };
+
+  let violations = profile(22.0).violations(&capabilities, &policy);
+  assert_eq!(violations.len(), 2);
+  assert_eq!(violations[0].rule, SpeedRule::Model);
+  assert_eq!(violations[1].rule, SpeedRule::Regional);
+
+  assert!(profile(18.0).violations(&capabilities, &policy).is_empty());
+}
+
+#[tokio::test]
+async fn it_reads_and_writes_speed_profile() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(0, Duration::from_millis(1));
+
+  let current = session.speed_profile().await.unwrap();
+  assert_eq!(current.limit_kmh, 20.0);
+
+  session.set_speed_profile(&profile(22.5)).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x73), 22500);
+  assert_eq!(session.speed_profile().await.unwrap(), profile(22.5));
+
+  transport.scooter().memory.write_u16(Bank::Motor, 0xB3, 2);
+  assert_eq!(session.speed_profile().await.unwrap().mode, WorkMode::Sport);
+}
+
+#[tokio::test]
+async fn it_rounds_limit_to_stored_meters_per_hour() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(0, Duration::from_millis(1));
+
+  session.set_speed_profile(&profile(20.0005)).await.unwrap();
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x73), 20001);
+  assert_eq!(session.speed_profile().await.unwrap().limit_kmh, 20.001);
+}
+
+#[tokio::test]
+async fn it_enforces_regional_maximum_in_strict_mode() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(0, Duration::from_millis(1));
+
+  session.set_speed_policy(SpeedPolicy { regional_max_kmh: Some(20.0), strict: false });
+  session.set_speed_profile(&profile(22.0)).await.unwrap();
+  assert_eq!(session.audit_speed_profile().await.unwrap().len(), 1);
+
+  session.set_speed_policy(SpeedPolicy { regional_max_kmh: Some(20.0), strict: true });
+  let error = session.set_speed_profile(&profile(24.0)).await.unwrap_err();
+  assert!(matches!(
+    error.downcast_ref::<SessionError>(),
+    Some(SessionError::SpeedLimitExceeded(violation)) if violation.rule == SpeedRule::Regional
+  ));
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x73), 22000);
+
+  let error = session.set_speed_profile(&profile(30.0)).await.unwrap_err();
+  assert!(error.to_string().contains("model maximum 25 km/h"), "{}", error);
+}
+
+#[tokio::test]
+async fn it_refuses_limits_scooter_can_not_store() {
+  let (transport, mut session) = common::simulated_session().await;
+  session.set_write_retries(0, Duration::from_millis(1));
+
+  for limit_kmh in [f32::NAN, -5.0, f32::INFINITY] {
+    let error = session.set_speed_profile(&profile(limit_kmh)).await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<SessionError>(), Some(SessionError::InvalidSpeedLimit(_))), "{}", error);
+  }
+
+  assert!(!profile(f32::NAN).is_valid());
+  assert!(!profile(70.0).is_valid());
+  assert_eq!(transport.scooter().memory.read_u16(Bank::Motor, 0x73), 20000);
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use m365::{
+  LoginRequest,
+  MiSession,
+  Reconnect,
+  SupervisedSession,
+  SupervisorConfig,
+  SessionEvent,
+  SessionError,
+  AuthToken
+};
+use m365::simulator::{SimulatedScooter, SimulatorTransport};
+
+use std::time::Duration;
+use async_trait::async_trait;
+
+#[derive(Clone)]
+struct SimulatorConnector {
+  transport: SimulatorTransport,
+  token: AuthToken
+}
+
+#[async_trait]
+impl Reconnect<SimulatorTransport> for SimulatorConnector {
+  async fn reconnect(&self) -> anyhow::Result<MiSession<SimulatorTransport>> {
+    self.transport.connect();
+
+    let mut login = LoginRequest::new(&self.transport, &self.token).await?;
+    login.start().await
+  }
+
+  fn is_connected(&self) -> bool {
+    self.transport.is_connected()
+  }
+}
+
+fn config() -> SupervisorConfig {
+  SupervisorConfig {
+    initial_backoff: Duration::from_millis(10),
+    max_backoff: Duration::from_millis(50),
+    max_reconnects: Some(3),
+    ..SupervisorConfig::default()
+  }
+}
+
+#[tokio::test]
+async fn it_reconnects_and_replays_command_after_disconnect() {
+  let transport = SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN));
+  let connector = SimulatorConnector { transport: transport.clone(), token: common::TOKEN };
+
+  let session = connector.reconnect().await.unwrap();
+  let mut supervised = SupervisedSession::new(connector, session, config());
+  let mut events = supervised.subscribe();
+
+  supervised.run(|session| Box::pin(session.motor_info())).await.unwrap();
+
+  transport.disconnect();
+  let motor_info = supervised.run(|session| Box::pin(session.motor_info())).await.unwrap();
+  assert_eq!(motor_info.total_distance_m, 1306083);
+
+  assert_eq!(events.recv().await.unwrap(), SessionEvent::Disconnected);
+  assert!(matches!(events.recv().await.unwrap(), SessionEvent::Reconnecting { attempt: 1, .. }));
+  assert_eq!(events.recv().await.unwrap(), SessionEvent::Connected);
+}
+
+#[tokio::test]
+async fn it_logs_in_again_when_message_counters_are_exhausted() {
+  let transport = SimulatorTransport::new(SimulatedScooter::with_token(&common::TOKEN));
+  let connector = SimulatorConnector { transport: transport.clone(), token: common::TOKEN };
+
+  let session = connector.reconnect().await.unwrap();
+  let mut supervised = SupervisedSession::new(connector, session, config());
+  let mut events = supervised.subscribe();
+
+  let mut exhausted = true;
+  let motor_info = supervised.run(|session| {
+    if std::mem::take(&mut exhausted) {
+      Box::pin(async { Err(SessionError::CounterExhausted.into()) })
+    } else {
+      Box::pin(session.motor_info())
+    }
+  }).await.unwrap();
+  assert_eq!(motor_info.total_distance_m, 1306083);
+
+  assert_eq!(events.recv().await.unwrap(), SessionEvent::Disconnected);
+  assert!(matches!(events.recv().await.unwrap(), SessionEvent::Reconnecting { attempt: 1, .. }));
+  assert_eq!(events.recv().await.unwrap(), SessionEvent::Connected);
+}
+
+#[derive(Clone)]
+struct BrokenConnector;
+
+#[async_trait]
+impl Reconnect<SimulatorTransport> for BrokenConnector {
+  async fn reconnect(&self) -> anyhow::Result<MiSession<SimulatorTransport>> {
+    Err(anyhow::anyhow!("Scooter is gone"))
+  }
+}
+
+#[tokio::test]
+async fn it_gives_up_after_max_reconnects() {
+  let (transport, session) = common::simulated_session().await;
+
+  let mut supervised = SupervisedSession::new(BrokenConnector, session, config());
+  let mut events = supervised.subscribe();
+
+  transport.disconnect();
+  assert!(supervised.run(|session| Box::pin(session.motor_info())).await.is_err());
+
+  let mut received = Vec::new();
+  while let Ok(event) = events.try_recv() {
+    received.push(event);
+  }
+
+  assert_eq!(received.first(), Some(&SessionEvent::Disconnected));
+  assert_eq!(received.last(), Some(&SessionEvent::GaveUp));
+  assert_eq!(received.iter().filter(|event| matches!(event, SessionEvent::ReconnectFailed { .. })).count(), 3);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use m365::{
+  SessionError,
+  TelemetryConfig,
+  TelemetryGroup
+};
+use m365::simulator::Bank;
+
+use std::time::Duration;
+use futures::StreamExt;
+
+fn config() -> TelemetryConfig {
+  TelemetryConfig {
+    motor_interval: Some(Duration::from_millis(10)),
+    trip_interval: Some(Duration::from_millis(10)),
+    distance_left_interval: None,
+    battery_interval: Some(Duration::from_millis(10)),
+    cell_voltages_interval: Some(Duration::from_secs(60))
+  }
+}
+
+#[tokio::test]
+async fn it_streams_merged_telemetry() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let mut stream = Box::pin(session.telemetry_stream(config()).unwrap());
+
+  let first = stream.next().await.unwrap().unwrap();
+  assert_eq!(first.motor.unwrap().total_distance_m, 1306083);
+  assert_eq!(first.trip.unwrap().distance_m, 2540);
+  assert_eq!(first.battery.unwrap().capacity, 7417);
+  assert_eq!(first.cell_voltages.unwrap().len(), 10);
+  assert!(first.distance_left_km.is_none());
+  assert!(first.failures.is_empty());
+
+  transport.scooter().memory.write_u16(Bank::Motor, 0xB4, 42);
+  transport.scooter().memory.write_u16(Bank::Battery, 0x40, 3500);
+
+  let second = stream.next().await.unwrap().unwrap();
+  assert_eq!(second.motor.unwrap().battery_percent, 42);
+  assert_eq!(second.cell_voltages.unwrap()[0], 3.676);
+  assert!(second.timestamp > first.timestamp);
+}
+
+#[tokio::test]
+async fn it_keeps_streaming_after_read_error() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let mut stream = Box::pin(session.telemetry_stream(config()).unwrap());
+  let first = stream.next().await.unwrap().unwrap();
+
+  // Reply too short to decode arrives before answer to next motor info read
+  transport.scooter().memory.write_u16(Bank::Motor, 0xB4, 42);
+  transport.scooter().queue_register_reply(Bank::Motor, 0xB0, 2);
+
+  let failed = stream.next().await.unwrap().unwrap();
+  assert_eq!(failed.failures.len(), 1);
+  assert_eq!(failed.failures[0].group, TelemetryGroup::Motor);
+  assert_eq!(failed.motor.unwrap().battery_percent, first.motor.unwrap().battery_percent);
+  assert!(failed.trip.is_some());
+
+  let recovered = stream.next().await.unwrap().unwrap();
+  assert!(recovered.failures.is_empty());
+  assert_eq!(recovered.motor.unwrap().battery_percent, 42);
+}
+
+#[tokio::test]
+async fn it_ends_stream_when_scooter_disconnects() {
+  let (transport, mut session) = common::simulated_session().await;
+
+  let mut stream = Box::pin(session.telemetry_stream(config()).unwrap());
+  assert!(stream.next().await.unwrap().is_ok());
+
+  transport.disconnect();
+  assert!(stream.next().await.unwrap().is_err());
+  assert!(stream.next().await.is_none());
+}
+
+#[tokio::test]
+async fn it_refuses_zero_interval() {
+  let (_, mut session) = common::simulated_session().await;
+
+  let config = TelemetryConfig { trip_interval: Some(Duration::ZERO), ..config() };
+  let error = session.telemetry_stream(config).err().unwrap();
+  assert!(matches!(
+    error.downcast_ref::<SessionError>(),
+    Some(SessionError::ZeroTelemetryInterval(TelemetryGroup::Trip))
+  ));
+}
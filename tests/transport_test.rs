@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use async_trait::async_trait;
+use btleplug::api::ValueNotification;
+use futures::stream;
+use hex_literal::hex;
+use uuid::Uuid;
+use m365::ScooterTransport;
+use m365::consts::Registers;
+use m365::protocol::{MiProtocol, ProtocolError};
+use m365::transport::NotificationStream;
+
+type Written = Arc<Mutex<Vec<(Uuid, Vec<u8>)>>>;
+
+#[derive(Clone, Default)]
+struct MemoryTransport {
+  written: Written,
+  incoming: Arc<Mutex<Vec<ValueNotification>>>,
+}
+
+#[async_trait]
+impl ScooterTransport for MemoryTransport {
+  async fn notifications(&self) -> Result<NotificationStream> {
+    let incoming : Vec<ValueNotification> = self.incoming.lock().unwrap().drain(..).collect();
+    Ok(Box::pin(stream::iter(incoming)))
+  }
+
+  async fn write(&self, reg: &Registers, data: &[u8]) -> Result<()> {
+    self.written.lock().unwrap().push((reg.to_uuid(), data.to_vec()));
+    Ok(())
+  }
+
+  async fn unsubscribe(&self) -> Result<()> {
+    Ok(())
+  }
+}
+
+#[tokio::test]
+async fn it_writes_mi_parcel_in_chunks() {
+  let transport = MemoryTransport::default();
+  let protocol = MiProtocol::new(&transport).await.unwrap();
+
+  protocol.write_mi_parcel(&Registers::AVDTP, &[0xaa; 20]).await.unwrap();
+
+  let written = transport.written.lock().unwrap();
+  assert_eq!(written.len(), 2);
+  assert_eq!(written[0].0, Registers::AVDTP.to_uuid());
+  assert_eq!(written[0].1[0..2], [0x01, 0x00]);
+  assert_eq!(written[0].1.len(), 20);
+  assert_eq!(written[1].1, vec![0x02, 0x00, 0xaa, 0xaa]);
+}
+
+#[tokio::test]
+async fn it_reads_nb_parcel_from_notifications() {
+  let transport = MemoryTransport::default();
+  let frame = hex!("55ab1001009a70888f3a27d8378bb07f7d8ce4cce88ab54a50595ad6c019c7f2");
+
+  for chunk in frame.chunks(20) {
+    transport.incoming.lock().unwrap().push(ValueNotification {
+      uuid: Registers::RX.to_uuid(),
+      value: chunk.to_vec()
+    });
+  }
+
+  let mut protocol = MiProtocol::new(&transport).await.unwrap();
+  let parcel = protocol.read_nb_parcel().await.unwrap();
+
+  assert_eq!(parcel, frame.to_vec());
+}
+
+impl MemoryTransport {
+  fn push_notification(&self, value: &[u8]) {
+    self.incoming.lock().unwrap().push(ValueNotification {
+      uuid: Registers::RX.to_uuid(),
+      value: value.to_vec()
+    });
+  }
+}
+
+#[tokio::test]
+async fn it_reads_nb_parcel_length_from_header() {
+  let transport = MemoryTransport::default();
+  let frame = hex!("55ab1001009a70888f3a27d8378bb07f7d8ce4cce88ab54a50595ad6c019c7f2");
+
+  transport.push_notification(&hex!("c019c7f2")); // leftover of older frame
+  for chunk in frame.chunks(20) {
+    transport.push_notification(chunk);
+  }
+  transport.push_notification(&frame[0..20]); // start of next frame stays in stream
+
+  let mut protocol = MiProtocol::new(&transport).await.unwrap();
+  let parcel = protocol.read_nb_parcel().await.unwrap();
+
+  assert_eq!(parcel, frame.to_vec());
+}
+
+#[tokio::test]
+async fn it_rejects_nb_parcel_with_invalid_checksum() {
+  let transport = MemoryTransport::default();
+  let frame = hex!("55ab1001009a70888f3a27d8378bb07f7d8ce4cce88ab54a50595ad6c019c700");
+
+  for chunk in frame.chunks(20) {
+    transport.push_notification(chunk);
+  }
+
+  let mut protocol = MiProtocol::new(&transport).await.unwrap();
+  let error = protocol.read_nb_parcel().await.unwrap_err();
+
+  assert!(matches!(error.downcast_ref::<ProtocolError>(), Some(ProtocolError::InvalidChecksum { .. })));
+}
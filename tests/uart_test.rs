@@ -1,7 +1,7 @@
 use hex_literal::hex;
 use tracing::Level;
 use tracing_subscriber::fmt::format::FmtSpan;
-use m365::mi_crypto::{EncryptionKey, encrypt_uart, crc16, decrypt_uart};
+use m365::mi_crypto::{EncryptionKey, encrypt_uart, crc16, decrypt_uart, uart_counter};
 
 #[test]
 fn it_crc16() {
@@ -11,6 +11,15 @@ fn it_crc16() {
   assert_eq!(crc, hex!("23fe"));
 }
 
+#[test]
+fn it_crc16_frame_with_255_byte_payload() {
+  // everything between header and crc of frame with 255 in length byte
+  let bytes = [0xff; 255 + 12];
+  let crc = crc16(&bytes);
+
+  assert_eq!(crc, hex!("0af6"));
+}
+
 #[test]
 fn it_encrypts_uart() {
   let encryption_key = EncryptionKey {
@@ -47,3 +56,17 @@ fn it_decrypt_uart() {
 
   assert_eq!("26354/00467353", text)
 }
+
+#[test]
+fn it_encrypts_uart_with_message_counter() {
+  let encryption_key = EncryptionKey {
+    key: hex!("5066d82368375a1f6a0a3eba1317b525"),
+    iv: hex!("28cee53e")
+  };
+
+  let cmd : [u8; 5] = hex!("032001100e");
+  let ct = encrypt_uart(&encryption_key, &cmd, 0x0102, None);
+
+  assert_eq!(uart_counter(&ct).unwrap(), 0x0102);
+  assert_eq!(decrypt_uart(&encryption_key, &ct).unwrap()[0..4], cmd[1..]);
+}